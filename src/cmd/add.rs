@@ -0,0 +1,426 @@
+use std::{
+    collections::BTreeSet,
+    env,
+    fs::{self, Metadata},
+    io,
+    path::Path,
+};
+
+use super::hash_object::HashObject;
+use crate::{
+    error::{Error, Result},
+    ignore::Ignore,
+    index::{
+        entry::{
+            mode_from_metadata, IndexEntry, StatData, MODE_EXECUTABLE, MODE_FILE, MODE_GITLINK,
+            MODE_SYMLINK,
+        },
+        Index,
+    },
+    obj::{
+        blob::Blob,
+        id::ObjectId,
+        store::{MemoryObjectDatabase, ObjectDatabase},
+        Object,
+    },
+    pathspec::Pathspec,
+    refs::resolve_ref,
+    repository::{nested_git_dir, Repository},
+};
+
+pub struct Add {
+    paths: Vec<String>,
+    /// `-A`: also stage new files and removals when no paths are given.
+    all: bool,
+    /// `-u`: only stage changes to tracked files, never new ones.
+    update: bool,
+    /// `-f`: also stage files that are ignored.
+    force: bool,
+    /// Only report what would be staged; no objects or index are written.
+    dry_run: bool,
+    verbose: bool,
+}
+
+impl Add {
+    pub fn new(
+        paths: Vec<String>,
+        all: bool,
+        update: bool,
+        force: bool,
+        dry_run: bool,
+        verbose: bool,
+    ) -> Self {
+        Self {
+            paths,
+            all,
+            update,
+            force,
+            dry_run,
+            verbose,
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut all = false;
+        let mut update = false;
+        let mut force = false;
+        let mut dry_run = false;
+        let mut verbose = false;
+        let mut paths = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-A" | "--all" => all = true,
+                "-u" | "--update" => update = true,
+                "-f" | "--force" => force = true,
+                "-n" | "--dry-run" => dry_run = true,
+                "-v" | "--verbose" => verbose = true,
+                "--" => {
+                    paths.extend(args.by_ref().cloned());
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => paths.push(arg.to_owned()),
+            }
+        }
+
+        if all && update {
+            return Err(Error::ParseCommand(String::from(
+                "options '-A' and '-u' cannot be used together",
+            )));
+        }
+
+        Ok(Self::new(paths, all, update, force, dry_run, verbose))
+    }
+
+    /// Stages the matching files, returning an `add '<path>'` or `remove '<path>'` line for
+    /// each entry whose content changed. Untracked files that are ignored are left out unless
+    /// forced, and naming one explicitly fails after the other files are staged.
+    pub fn inner(&self, repo: &Repository, cwd: &Path) -> Result<Vec<String>> {
+        let work_tree = repo.require_work_tree()?;
+        let pathspec = Pathspec::new(work_tree, cwd, &self.paths)?;
+        let mut index = repo.index()?;
+        let mut ignore = match self.force {
+            true => None,
+            false => Some(Ignore::new(work_tree, repo.git_dir(), repo.config())?),
+        };
+        let mut matched = vec![false; pathspec.items().len()];
+        let mut candidates = BTreeSet::new();
+
+        let mut record = |path: &str| {
+            for (item, matched) in pathspec.items().iter().zip(&mut matched) {
+                *matched |= item.matches(path).is_some();
+            }
+
+            candidates.insert(path.to_owned());
+        };
+
+        for entry in index.entries() {
+            if pathspec.matches(&entry.path) {
+                record(&entry.path);
+            }
+        }
+
+        if !self.update {
+            for path in untracked_files(work_tree, &pathspec, &index, ignore.as_mut())? {
+                record(&path);
+            }
+        }
+
+        let mut ignored = Vec::new();
+
+        if let Some(ignore) = &mut ignore {
+            for item in pathspec.items() {
+                let full_path = work_tree.join(&item.path);
+
+                if !item.path.is_empty()
+                    && index.entry(&item.path, 0).is_none()
+                    && full_path.symlink_metadata().is_ok()
+                    && ignore.is_ignored(&item.path, full_path.is_dir())?
+                {
+                    ignored.push(item.path.clone());
+                }
+            }
+        }
+
+        // Like git, paths that exist but have nothing to stage, such as untracked files with
+        // `-u`, are not an error.
+        for (item, matched) in pathspec.items().iter().zip(&mut matched) {
+            *matched |= work_tree.join(&item.path).exists();
+        }
+
+        pathspec.check_matched(&matched)?;
+
+        let memory = MemoryObjectDatabase::new(repo.object_format());
+        let odb = match self.dry_run {
+            true => &memory as &dyn ObjectDatabase,
+            false => repo.odb(),
+        };
+        let trust_executable = repo.config().get_bool("core.filemode")?.unwrap_or(true);
+        let mut output = Vec::new();
+        let mut changed = false;
+
+        for path in candidates {
+            let full_path = work_tree.join(&path);
+            let metadata = match fs::symlink_metadata(&full_path) {
+                Ok(metadata) => Some(metadata),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+
+            let existing = index.entry(&path, 0).filter(|e| !e.intent_to_add);
+            let conflicted = index
+                .entries()
+                .iter()
+                .any(|e| e.path == path && e.stage > 0);
+
+            let metadata = match metadata {
+                Some(metadata) if metadata.is_dir() => {
+                    let gitlink = existing.is_some_and(|e| e.mode == MODE_GITLINK);
+
+                    if !gitlink && nested_git_dir(&full_path).is_none() {
+                        output.push(format!("remove '{}'", path));
+                        changed |= index.remove(&path);
+                        continue;
+                    }
+
+                    // Submodules are staged at the commit checked out in them, and left as
+                    // they are when none is, as when they are not initialized.
+                    let head = match nested_git_dir(&full_path) {
+                        Some(git_dir) => resolve_ref(&git_dir, "HEAD")?.1,
+                        None => None,
+                    };
+
+                    if let Some(id) = head {
+                        if conflicted || existing.is_none_or(|e| e.id != id || !gitlink) {
+                            output.push(format!("add '{}'", path));
+                            remove_conflicts(&mut index, &path);
+                            index.add(IndexEntry::new(&path, id, MODE_GITLINK));
+                            changed = true;
+                        }
+                    }
+                    continue;
+                }
+                Some(metadata) => metadata,
+                None => {
+                    output.push(format!("remove '{}'", path));
+                    changed |= index.remove(&path);
+                    continue;
+                }
+            };
+
+            if existing.is_some_and(|e| !conflicted && index.is_up_to_date(e, &metadata)) {
+                continue;
+            }
+
+            let mut mode = mode_from_metadata(&metadata);
+            // Without a trustworthy executable bit, files keep the mode they were added with.
+            if !trust_executable && mode != MODE_SYMLINK {
+                mode = existing
+                    .map(|e| e.mode)
+                    .filter(|&mode| mode == MODE_EXECUTABLE)
+                    .unwrap_or(MODE_FILE);
+            }
+
+            let id = hash_file(odb, &full_path, &metadata)?;
+
+            if conflicted || existing.is_none_or(|e| e.id != id || e.mode != mode) {
+                output.push(format!("add '{}'", path));
+            }
+
+            let mut entry = IndexEntry::new(&path, id, mode);
+            entry.stat = StatData::from_metadata(&metadata);
+
+            remove_conflicts(&mut index, &path);
+            index.add(entry);
+            changed = true;
+        }
+
+        if changed && !self.dry_run {
+            index.write(&repo.index_path())?;
+        }
+
+        if !ignored.is_empty() {
+            ignored.sort();
+            ignored.dedup();
+
+            return Err(Error::Generic(format!(
+                "The following paths are ignored by one of your .gitignore files:\n{}\n\
+                 hint: Use -f if you really want to add them.",
+                ignored.join("\n")
+            )));
+        }
+
+        Ok(output)
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        if self.paths.is_empty() && !self.all && !self.update {
+            eprintln!("Nothing specified, nothing added.");
+
+            return Ok(());
+        }
+
+        let output = self.inner(repo, &env::current_dir()?)?;
+
+        if self.dry_run || self.verbose {
+            for line in output {
+                println!("{}", line);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the content of the work tree file at `path` as a blob: the file itself, or the target
+/// of a symlink.
+pub fn hash_file(odb: &dyn ObjectDatabase, path: &Path, metadata: &Metadata) -> Result<ObjectId> {
+    if metadata.is_symlink() {
+        let target = fs::read_link(path)?;
+        let target = target
+            .to_str()
+            .ok_or_else(|| Error::OsString(target.clone().into_os_string()))?;
+
+        return odb.write(&Object::from_blob(Blob::new(target.as_bytes())));
+    }
+
+    HashObject::new(path.to_string_lossy()).inner(odb)
+}
+
+/// Lists the files and symlinks in the work tree that match `pathspec` but are not in `index`,
+/// skipping `.git`, nested repositories and, if given rules, what they ignore.
+fn untracked_files(
+    work_tree: &Path,
+    pathspec: &Pathspec,
+    index: &Index,
+    mut ignore: Option<&mut Ignore>,
+) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut pending = vec![String::new()];
+
+    while let Some(dir) = pending.pop() {
+        for dir_entry in fs::read_dir(work_tree.join(&dir))? {
+            let dir_entry = dir_entry?;
+            let name = dir_entry.file_name();
+            let name = name
+                .to_str()
+                .ok_or_else(|| Error::OsString(dir_entry.file_name()))?;
+
+            if name == ".git" {
+                continue;
+            }
+
+            let path = match dir.is_empty() {
+                true => name.to_owned(),
+                false => format!("{}/{}", dir, name),
+            };
+
+            let is_dir = dir_entry.file_type()?.is_dir();
+            let mut is_ignored = |path: &str| match &mut ignore {
+                Some(ignore) => ignore.is_excluded(path, is_dir),
+                None => Ok(false),
+            };
+
+            if is_dir {
+                if pathspec.may_match_inside(&path)
+                    && !dir_entry.path().join(".git").exists()
+                    && !is_ignored(&path)?
+                {
+                    pending.push(path);
+                }
+            } else if pathspec.matches(&path)
+                && index.entry(&path, 0).is_none()
+                && !is_ignored(&path)?
+            {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Removes the entries that cannot coexist with a file at `path`: files where its leading
+/// directories are, and anything below it.
+fn remove_conflicts(index: &mut Index, path: &str) {
+    let mut parent = path;
+
+    while let Some((dir, _)) = parent.rsplit_once('/') {
+        index.remove(dir);
+        parent = dir;
+    }
+
+    let children = index
+        .entries()
+        .iter()
+        .filter(|e| {
+            e.path
+                .strip_prefix(path)
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .map(|e| e.path.clone())
+        .collect::<Vec<_>>();
+
+    for child in children {
+        index.remove(&child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::sha::ObjectFormat;
+
+    #[test]
+    fn test_add_skips_ignored_files_and_keeps_gitlinks() {
+        let work_tree = env::temp_dir().join(format!("add-test-{}", process::id()));
+        let repo = Repository::init(&work_tree, ObjectFormat::Sha1).unwrap();
+
+        fs::create_dir_all(work_tree.join("build")).unwrap();
+        fs::write(work_tree.join(".gitignore"), "junk\nbuild/\n").unwrap();
+        fs::write(work_tree.join("a"), "a\n").unwrap();
+        fs::write(work_tree.join("junk"), "junk\n").unwrap();
+        fs::write(work_tree.join("build/out"), "out\n").unwrap();
+
+        let old = ObjectId::empty_tree(ObjectFormat::Sha1);
+        let new = ObjectId::empty_blob(ObjectFormat::Sha1);
+        Repository::init(&work_tree.join("sub"), ObjectFormat::Sha1).unwrap();
+        fs::write(work_tree.join("sub/.git/HEAD"), format!("{}\n", new)).unwrap();
+        fs::create_dir(work_tree.join("uninitialized")).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add(IndexEntry::new("sub", old, MODE_GITLINK));
+        index.add(IndexEntry::new("uninitialized", old, MODE_GITLINK));
+        index.write(&repo.index_path()).unwrap();
+
+        let add = |paths: &[&str], force: bool| {
+            let paths = paths.iter().map(|p| p.to_string()).collect();
+            Add::new(paths, true, false, force, false, false).inner(&repo, &work_tree)
+        };
+
+        assert_eq!(
+            vec!["add '.gitignore'", "add 'a'", "add 'sub'"],
+            add(&[], false).unwrap()
+        );
+
+        let index = repo.index().unwrap();
+        let paths = index.entries().iter().map(|e| e.path.as_str());
+        assert_eq!(
+            vec![".gitignore", "a", "sub", "uninitialized"],
+            paths.collect::<Vec<_>>()
+        );
+        assert_eq!(new, index.entry("sub", 0).unwrap().id);
+        assert_eq!(old, index.entry("uninitialized", 0).unwrap().id);
+
+        assert!(add(&["junk"], false).is_err());
+        assert!(add(&["build/out"], false).is_err());
+        assert_eq!(vec!["add 'junk'"], add(&["junk"], true).unwrap());
+
+        fs::remove_dir_all(work_tree).unwrap();
+    }
+}
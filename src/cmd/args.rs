@@ -1,4 +1,37 @@
-pub fn get_named_arg<'a>(args: &[String], name: &'static str) -> (Vec<String>, Option<String>) {
+use std::env;
+
+use crate::{Error, Result};
+
+/// Applies the options that come before the command name, such as `-C <path>`, and returns
+/// the remaining args with the program name still in front.
+pub fn apply_global_options(args: &[String]) -> Result<Vec<String>> {
+    let mut rest = args.iter().skip(1);
+    let mut remaining = args.iter().take(1).cloned().collect::<Vec<_>>();
+
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-C" => {
+                let path = rest.next().ok_or_else(|| {
+                    Error::ParseCommand(String::from("no directory given for -C"))
+                })?;
+
+                if !path.is_empty() {
+                    env::set_current_dir(path)
+                        .map_err(|_| Error::Generic(format!("cannot change to '{}'", path)))?;
+                }
+            }
+            _ => {
+                remaining.push(arg.to_owned());
+                remaining.extend(rest.cloned());
+                break;
+            }
+        }
+    }
+
+    Ok(remaining)
+}
+
+pub fn get_named_arg(args: &[String], name: &'static str) -> (Vec<String>, Option<String>) {
     let args = args.to_vec();
 
     let name_idx = match args.iter().position(|a| a == name) {
@@ -23,14 +56,14 @@ mod tests {
 
     #[test]
     fn test_get_named_arg() {
-        assert_eq!((vec![], None), get_named_arg(&vec![], "-p"));
+        assert_eq!((vec![], None), get_named_arg(&[], "-p"));
         assert_eq!(
             (vec![String::from("-p")], None),
-            get_named_arg(&vec![String::from("-p")], "-p")
+            get_named_arg(&[String::from("-p")], "-p")
         );
         assert_eq!(
             (vec![], Some(String::from("1212"))),
-            get_named_arg(&vec![String::from("-p"), String::from("1212")], "-p")
+            get_named_arg(&[String::from("-p"), String::from("1212")], "-p")
         );
         assert_eq!(
             (
@@ -38,7 +71,7 @@ mod tests {
                 Some(String::from("1212"))
             ),
             get_named_arg(
-                &vec![
+                &[
                     String::from("-w"),
                     String::from("-p"),
                     String::from("1212"),
@@ -2,10 +2,12 @@ use std::io::{self, Write};
 
 use crate::{
     error::{Error, Result},
-    obj::store,
+    repository::Repository,
+    revision,
 };
 
 pub struct CatFile {
+    /// Revision naming the blob, such as `HEAD:README` or a blob id.
     blob_sha: String,
 }
 
@@ -17,30 +19,37 @@ impl CatFile {
     }
 
     pub fn parse(args: &[String]) -> Result<Self> {
-        match args.get(0).map(|a| a.as_str()) {
+        match args.first().map(|a| a.as_str()) {
             Some("-p") => {}
             _ => return Err(Error::ParseCommand(String::from("option must be \"-p\""))),
         }
 
         match args.get(1) {
-            Some(blob_sha) => Ok(Self {
-                blob_sha: blob_sha.to_owned(),
-            }),
+            Some(blob_sha) => Ok(Self::new(blob_sha)),
             None => Err(Error::ParseCommand(String::from("missing blob sha arg"))),
         }
     }
 
-    pub fn inner(&self) -> Result<Vec<u8>> {
-        store::read(&self.blob_sha)?
+    pub fn inner(&self, repo: &Repository) -> Result<Vec<u8>> {
+        repo.odb()
+            .read(&revision::resolve(repo, &self.blob_sha)?)?
             .as_blob()
             .map(|b| b.to_bytes())
             .ok_or_else(|| Error::Generic(String::from("git object must be blob")))
     }
 
-    pub fn exec(self) -> Result<()> {
-        let bytes = self.inner()?;
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        let mut reader = repo
+            .odb()
+            .read_stream(&revision::resolve(repo, &self.blob_sha)?)?;
 
-        io::stdout().write_all(&bytes)?;
+        if reader.header.kind != "blob" {
+            return Err(Error::Generic(String::from("git object must be blob")));
+        }
+
+        let mut stdout = io::stdout().lock();
+        io::copy(&mut reader, &mut stdout)?;
+        stdout.flush()?;
 
         Ok(())
     }
@@ -1,54 +1,88 @@
-use super::args::get_named_arg;
 use crate::{
-    obj::{commit::Commit, store, user::User, Object},
+    obj::{
+        commit::Commit,
+        id::ObjectId,
+        user::{Role, User},
+        Object,
+    },
+    repository::Repository,
+    revision::{self, peel},
     Error, Result,
 };
 
 pub struct CommitTree {
-    tree_sha: String,
-    commit_sha: Option<String>,
+    /// Revision naming the tree, such as `HEAD^{tree}` or a tree id.
+    tree: String,
+    /// Revisions naming the parent commits, in order.
+    parents: Vec<String>,
     msg: String,
 }
 
 impl CommitTree {
-    pub fn new(tree_sha: impl ToString, commit_sha: Option<impl ToString>, msg: String) -> Self {
+    pub fn new(tree: impl ToString, parents: Vec<String>, msg: impl ToString) -> Self {
         Self {
-            tree_sha: tree_sha.to_string(),
-            commit_sha: commit_sha.map(|c| c.to_string()),
-            msg,
+            tree: tree.to_string(),
+            parents,
+            msg: msg.to_string(),
         }
     }
 
     pub fn parse(args: &[String]) -> Result<Self> {
-        let tree_sha = args
-            .get(0)
-            .ok_or_else(|| Error::ParseCommand(String::from("arg tree sha is required")))?;
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut msg = None;
+        let mut args = args.iter();
 
-        let (args, commit_sha) = get_named_arg(args, "-p");
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| Error::ParseCommand(format!("missing {} arg", name)))
+            };
 
-        let (_, msg) = get_named_arg(&args, "-m");
+            match arg.as_str() {
+                "-p" => parents.push(value("parent")?),
+                "-m" => msg = Some(value("message")?),
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ if tree.is_none() => tree = Some(arg.to_owned()),
+                _ => return Err(Error::ParseCommand(format!("unexpected arg: {}", arg))),
+            }
+        }
 
+        let tree =
+            tree.ok_or_else(|| Error::ParseCommand(String::from("arg tree sha is required")))?;
         let msg =
             msg.ok_or_else(|| Error::ParseCommand(String::from("arg message is required")))?;
 
-        Ok(Self::new(tree_sha, commit_sha, msg))
+        Ok(Self::new(tree, parents, msg))
     }
 
-    pub fn inner(&self) -> Result<String> {
+    pub fn inner(&self, repo: &Repository) -> Result<ObjectId> {
+        let config = repo.config();
+
+        let tree_sha = peel(repo, revision::resolve(repo, &self.tree)?, "tree")?;
+        let parent_shas = self
+            .parents
+            .iter()
+            .map(|parent| peel(repo, revision::resolve(repo, parent)?, "commit"))
+            .collect::<Result<_>>()?;
+
         let commit = Commit::new(
-            &self.tree_sha,
-            self.commit_sha.as_deref(),
+            tree_sha,
+            parent_shas,
             &self.msg,
-            User::default(),
-            User::default(),
+            User::signature(Role::Author, config)?,
+            User::signature(Role::Committer, config)?,
         );
         let object = Object::from_commit(commit);
 
-        store::write(&object)
+        repo.odb().write(&object)
     }
 
-    pub fn exec(self) -> Result<()> {
-        let sha = self.inner()?;
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        let sha = self.inner(repo)?;
 
         println!("{}", sha);
 
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+
+use super::args::get_named_arg;
+use crate::{
+    config::{expand_path, file::ConfigFile, parse_bool, parse_int, Config, Scope},
+    repository::Repository,
+    Error, Result,
+};
+
+pub enum ConfigAction {
+    Get { key: String, all: bool },
+    Set { key: String, value: String },
+    Unset { key: String, all: bool },
+    List,
+}
+
+pub enum ConfigTarget {
+    Scope(Scope),
+    File(PathBuf),
+}
+
+#[derive(Clone, Copy)]
+pub enum ValueType {
+    Bool,
+    Int,
+    Path,
+}
+
+pub struct ConfigCmd {
+    action: ConfigAction,
+    target: Option<ConfigTarget>,
+    value_type: Option<ValueType>,
+}
+
+impl ConfigCmd {
+    pub fn new(
+        action: ConfigAction,
+        target: Option<ConfigTarget>,
+        value_type: Option<ValueType>,
+    ) -> Self {
+        Self {
+            action,
+            target,
+            value_type,
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let (args, file) = get_named_arg(args, "--file");
+        let (args, value_type) = get_named_arg(&args, "--type");
+
+        let mut target = file.map(|f| ConfigTarget::File(PathBuf::from(f)));
+        let mut value_type = value_type.map(|t| parse_value_type(&t)).transpose()?;
+        let mut action = None;
+        let mut positional = Vec::new();
+
+        for arg in args {
+            match arg.as_str() {
+                "--system" => target = Some(ConfigTarget::Scope(Scope::System)),
+                "--global" => target = Some(ConfigTarget::Scope(Scope::Global)),
+                "--local" => target = Some(ConfigTarget::Scope(Scope::Local)),
+                "--worktree" => target = Some(ConfigTarget::Scope(Scope::Worktree)),
+                "--bool" | "--int" | "--path" => value_type = Some(parse_value_type(&arg[2..])?),
+                "--get" | "--get-all" | "--set" | "--unset" | "--unset-all" | "--list" | "-l" => {
+                    if action.replace(arg).is_some() {
+                        return Err(Error::ParseCommand(String::from(
+                            "only one action at a time",
+                        )));
+                    }
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let mut next_arg = |name: &str| {
+            positional
+                .next()
+                .ok_or_else(|| Error::ParseCommand(format!("missing {} arg", name)))
+        };
+
+        let action = match action.as_deref() {
+            Some("--get") => ConfigAction::Get {
+                key: next_arg("key")?,
+                all: false,
+            },
+            Some("--get-all") => ConfigAction::Get {
+                key: next_arg("key")?,
+                all: true,
+            },
+            Some("--set") => ConfigAction::Set {
+                key: next_arg("key")?,
+                value: next_arg("value")?,
+            },
+            Some("--unset") => ConfigAction::Unset {
+                key: next_arg("key")?,
+                all: false,
+            },
+            Some("--unset-all") => ConfigAction::Unset {
+                key: next_arg("key")?,
+                all: true,
+            },
+            Some(_) => ConfigAction::List,
+            None => {
+                let key = next_arg("key")?;
+
+                match next_arg("value") {
+                    Ok(value) => ConfigAction::Set { key, value },
+                    Err(_) => ConfigAction::Get { key, all: false },
+                }
+            }
+        };
+
+        if let Some(arg) = positional.next() {
+            return Err(Error::ParseCommand(format!("unexpected arg: {}", arg)));
+        }
+
+        Ok(Self::new(action, target, value_type))
+    }
+
+    fn load(&self, git_dir: Option<&Path>) -> Result<Config> {
+        match &self.target {
+            None => Config::load(git_dir),
+            Some(ConfigTarget::File(path)) => Config::load_file(path, Scope::Command, git_dir),
+            Some(ConfigTarget::Scope(scope)) => {
+                let mut config = Config::default();
+
+                for path in scope.paths(git_dir) {
+                    config.extend(Config::load_file(&path, *scope, git_dir)?);
+                }
+
+                Ok(config)
+            }
+        }
+    }
+
+    /// Returns the file that `--set`/`--unset` write to, the local config by default.
+    fn write_path(&self, git_dir: Option<&Path>) -> Result<PathBuf> {
+        let scope = match &self.target {
+            Some(ConfigTarget::File(path)) => return Ok(path.to_owned()),
+            Some(ConfigTarget::Scope(scope)) => *scope,
+            None => Scope::Local,
+        };
+
+        scope
+            .paths(git_dir)
+            .pop()
+            .ok_or_else(|| Error::Config(String::from("no config file to write to")))
+    }
+
+    fn format_value(&self, key: &str, value: Option<&str>) -> Result<String> {
+        let invalid = |kind| {
+            Error::Config(format!(
+                "bad {} config value '{}' for '{}'",
+                kind,
+                value.unwrap_or_default(),
+                key
+            ))
+        };
+
+        let value = match (self.value_type, value) {
+            (Some(ValueType::Bool), None) => String::from("true"),
+            (_, None) => String::new(),
+            (None, Some(value)) => value.to_owned(),
+            (Some(ValueType::Bool), Some(value)) => parse_bool(value)
+                .ok_or_else(|| invalid("boolean"))?
+                .to_string(),
+            (Some(ValueType::Int), Some(value)) => parse_int(value)
+                .ok_or_else(|| invalid("numeric"))?
+                .to_string(),
+            (Some(ValueType::Path), Some(value)) => {
+                expand_path(value)?.to_string_lossy().into_owned()
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Runs the action against the repository at `git_dir`, or outside of any repository.
+    pub fn inner(&self, git_dir: Option<&Path>) -> Result<Vec<String>> {
+        let lines = match &self.action {
+            ConfigAction::Get { key, all } => {
+                let config = self.load(git_dir)?;
+
+                let mut values = config.values(key)?.collect::<Vec<_>>();
+                if !all {
+                    values = values.split_off(values.len().saturating_sub(1));
+                }
+
+                if values.is_empty() {
+                    return Err(Error::Config(format!("key {} is not set", key)));
+                }
+
+                values
+                    .into_iter()
+                    .map(|entry| self.format_value(key, entry.value.as_deref()))
+                    .collect::<Result<_>>()?
+            }
+            ConfigAction::List => self
+                .load(git_dir)?
+                .entries()
+                .iter()
+                .map(|entry| match &entry.value {
+                    Some(value) => format!("{}={}", entry.key, value),
+                    None => entry.key.to_string(),
+                })
+                .collect(),
+            ConfigAction::Set { key, value } => {
+                let path = self.write_path(git_dir)?;
+
+                let mut file = ConfigFile::open(&path)?;
+                file.set(&key.parse()?, value)?;
+                file.save(&path)?;
+
+                Vec::new()
+            }
+            ConfigAction::Unset { key, all } => {
+                let path = self.write_path(git_dir)?;
+
+                let mut file = ConfigFile::open(&path)?;
+                if file.unset(&key.parse()?, *all)? == 0 {
+                    return Err(Error::Config(format!("key {} is not set", key)));
+                }
+                file.save(&path)?;
+
+                Vec::new()
+            }
+        };
+
+        Ok(lines)
+    }
+
+    pub fn exec(self, repo: Option<&Repository>) -> Result<()> {
+        for line in self.inner(repo.map(Repository::git_dir))? {
+            println!("{}", line);
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_value_type(value_type: &str) -> Result<ValueType> {
+    match value_type {
+        "bool" => Ok(ValueType::Bool),
+        "int" => Ok(ValueType::Int),
+        "path" => Ok(ValueType::Path),
+        _ => Err(Error::ParseCommand(format!(
+            "unknown value type: {}",
+            value_type
+        ))),
+    }
+}
@@ -0,0 +1,242 @@
+use std::cmp::Ordering;
+
+use crate::{
+    config::wildmatch,
+    error::{Error, Result},
+    obj::Object,
+    refs::{list_refs, shorten_ref_name, Reference},
+    repository::Repository,
+};
+
+const DEFAULT_FORMAT: &str = "%(objectname) %(objecttype)\t%(refname)";
+
+/// Abbreviation length used by `:short` atoms.
+const SHORT_ID_LEN: usize = 7;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FormatPart {
+    Literal(String),
+    /// A `%(atom)` placeholder, such as `refname:short` or `*objectname`.
+    Atom(String),
+}
+
+pub struct ForEachRef {
+    patterns: Vec<String>,
+    format: Vec<FormatPart>,
+    /// Sort keys, the last one taking precedence; a leading `-` reverses the order.
+    sort: Vec<String>,
+    count: Option<usize>,
+}
+
+impl ForEachRef {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut patterns = Vec::new();
+        let mut format = None;
+        let mut sort = Vec::new();
+        let mut count = None;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let (option, value) = match arg.split_once('=') {
+                Some((option, value)) if arg.starts_with("--") => (option, Some(value.to_owned())),
+                _ => (arg.as_str(), None),
+            };
+
+            let mut value = || {
+                value
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| Error::ParseCommand(format!("missing {} value", option)))
+            };
+
+            match option {
+                "--format" => format = Some(parse_format(&value()?)?),
+                "--sort" => sort.push(value()?),
+                "--count" => {
+                    count = Some(
+                        value()?
+                            .parse()
+                            .map_err(|_| Error::ParseCommand(format!("invalid count: {}", arg)))?,
+                    )
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => patterns.push(arg.to_owned()),
+            }
+        }
+
+        Ok(Self {
+            patterns,
+            format: match format {
+                Some(format) => format,
+                None => parse_format(DEFAULT_FORMAT)?,
+            },
+            sort,
+            count,
+        })
+    }
+
+    /// A pattern matches a ref literally, as a prefix ending at a `/`, or as a glob.
+    fn matches(&self, name: &str) -> bool {
+        self.patterns.is_empty()
+            || self.patterns.iter().any(|pattern| {
+                let prefix = pattern.trim_end_matches('/');
+
+                name == prefix
+                    || name
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                    || wildmatch(pattern, name, false)
+            })
+    }
+
+    pub fn inner(&self, repo: &Repository) -> Result<Vec<String>> {
+        let mut refs = list_refs(repo.git_dir(), "refs/")?
+            .into_iter()
+            .filter(|r| self.matches(&r.name))
+            .map(|r| Ok((atom_values(repo, &r, &self.sort)?, r)))
+            .collect::<Result<Vec<_>>>()?;
+
+        for (i, key) in self.sort.iter().enumerate() {
+            let reverse = key.starts_with('-');
+
+            refs.sort_by(|(a, _), (b, _)| {
+                let ordering = compare_values(&a[i], &b[i]);
+
+                if reverse {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+
+        refs.into_iter()
+            .take(self.count.unwrap_or(usize::MAX))
+            .map(|(_, reference)| {
+                let mut line = String::new();
+
+                for part in &self.format {
+                    match part {
+                        FormatPart::Literal(text) => line.push_str(text),
+                        FormatPart::Atom(atom) => {
+                            line.push_str(&atom_value(repo, &reference, atom)?)
+                        }
+                    }
+                }
+
+                Ok(line)
+            })
+            .collect()
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        for line in self.inner(repo)? {
+            println!("{}", line);
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a format into literals and `%(atom)`s, decoding `%%` and `%xx` hex escapes.
+pub fn parse_format(format: &str) -> Result<Vec<FormatPart>> {
+    let mut parts = Vec::new();
+    let mut literal = Vec::new();
+    let mut rest = format;
+
+    while let Some(i) = rest.find('%') {
+        literal.extend_from_slice(&rest.as_bytes()[..i]);
+        rest = &rest[i + 1..];
+
+        if let Some(atom) = rest.strip_prefix('(') {
+            let end = atom.find(')').ok_or_else(|| {
+                Error::ParseCommand(format!("malformed format string {}", format))
+            })?;
+
+            if !literal.is_empty() {
+                parts.push(FormatPart::Literal(String::from_utf8(
+                    literal.split_off(0),
+                )?));
+            }
+            parts.push(FormatPart::Atom(atom[..end].to_owned()));
+            rest = &atom[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('%') {
+            literal.push(b'%');
+            rest = after;
+        } else if let Some(byte) = rest
+            .get(..2)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            literal.push(byte);
+            rest = &rest[2..];
+        } else {
+            literal.push(b'%');
+        }
+    }
+
+    literal.extend_from_slice(rest.as_bytes());
+    if !literal.is_empty() {
+        parts.push(FormatPart::Literal(String::from_utf8(literal)?));
+    }
+
+    Ok(parts)
+}
+
+fn atom_values(repo: &Repository, reference: &Reference, keys: &[String]) -> Result<Vec<String>> {
+    keys.iter()
+        .map(|key| atom_value(repo, reference, key.trim_start_matches('-')))
+        .collect()
+}
+
+/// Compares numerically when both values are numbers, such as `objectsize`.
+fn compare_values(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+fn atom_value(repo: &Repository, reference: &Reference, atom: &str) -> Result<String> {
+    let odb = repo.odb();
+
+    // `*` atoms describe the object an annotated tag points to, and are empty for other refs.
+    let (id, atom) = match atom.strip_prefix('*') {
+        Some(atom) => match odb.read(&reference.id)? {
+            Object::Tag(tag) => (*tag.object_sha(), atom),
+            _ => return Ok(String::new()),
+        },
+        None => (reference.id, atom),
+    };
+
+    let value = match atom {
+        "refname" => reference.name.clone(),
+        "refname:short" => shorten_ref_name(&reference.name).to_owned(),
+        "objectname" => id.to_string(),
+        "objectname:short" => odb.shortest_unique_prefix(&id, SHORT_ID_LEN)?,
+        "objecttype" => odb.read_header(&id)?.kind,
+        "objectsize" => odb.read_header(&id)?.size.to_string(),
+        _ => return Err(Error::ParseCommand(format!("unknown field name: {}", atom))),
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            vec![
+                FormatPart::Atom(String::from("objectname")),
+                FormatPart::Literal(String::from(" 100%\t")),
+                FormatPart::Atom(String::from("*refname:short")),
+            ],
+            parse_format("%(objectname) 100%%%09%(*refname:short)").unwrap()
+        );
+        assert!(parse_format("%(refname").is_err());
+    }
+}
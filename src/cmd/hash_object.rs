@@ -1,8 +1,9 @@
-use std::fs;
+use std::fs::{self, File};
 
 use crate::{
     error::{Error, Result},
-    obj::{blob::Blob, store, Object},
+    obj::{id::ObjectId, store::ObjectDatabase},
+    repository::Repository,
 };
 
 pub struct HashObject {
@@ -17,7 +18,7 @@ impl HashObject {
     }
 
     pub fn parse(args: &[String]) -> Result<Self> {
-        match args.get(0).map(|a| a.as_str()) {
+        match args.first().map(|a| a.as_str()) {
             Some("-w") => {}
             _ => return Err(Error::ParseCommand(String::from("option must be \"-w\""))),
         }
@@ -30,17 +31,15 @@ impl HashObject {
         }
     }
 
-    pub fn inner(&self) -> Result<String> {
-        let content = fs::read(&self.file)?;
+    pub fn inner(&self, odb: &dyn ObjectDatabase) -> Result<ObjectId> {
+        let size = fs::metadata(&self.file)?.len();
+        let mut file = File::open(&self.file)?;
 
-        let blob = Blob::new(content.as_slice());
-        let object = Object::from_blob(blob);
-
-        store::write(&object)
+        odb.write_stream("blob", size, &mut file)
     }
 
-    pub fn exec(self) -> Result<()> {
-        let sha = self.inner()?;
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        let sha = self.inner(repo.odb())?;
 
         println!("{}", sha);
 
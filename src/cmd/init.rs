@@ -1,15 +1,55 @@
-use std::fs;
+use std::{env, path::PathBuf};
 
-use crate::error::Result;
+use crate::{
+    error::{Error, Result},
+    repository::Repository,
+    sha::ObjectFormat,
+};
 
-pub struct Init;
+#[derive(Default)]
+pub struct Init {
+    object_format: ObjectFormat,
+    directory: Option<PathBuf>,
+}
 
 impl Init {
+    pub fn new(object_format: ObjectFormat, directory: Option<PathBuf>) -> Self {
+        Self {
+            object_format,
+            directory,
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut init = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let object_format = match arg.strip_prefix("--object-format") {
+                Some("") => args.next().map(String::as_str),
+                Some(value) => value.strip_prefix('='),
+                None if !arg.starts_with('-') && init.directory.is_none() => {
+                    init.directory = Some(PathBuf::from(arg));
+                    continue;
+                }
+                None => return Err(Error::ParseCommand(format!("unknown option: {}", arg))),
+            };
+
+            init.object_format = object_format
+                .ok_or_else(|| Error::ParseCommand(String::from("missing object format arg")))?
+                .parse()?;
+        }
+
+        Ok(init)
+    }
+
     pub fn exec(self) -> Result<()> {
-        fs::create_dir(".git")?;
-        fs::create_dir(".git/objects")?;
-        fs::create_dir(".git/refs")?;
-        fs::write(".git/HEAD", "ref: refs/heads/master\n")?;
+        let work_tree = match self.directory {
+            Some(directory) => env::current_dir()?.join(directory),
+            None => env::current_dir()?,
+        };
+
+        Repository::init(&work_tree, self.object_format)?;
 
         println!("initialized git directory");
 
@@ -1,15 +1,17 @@
 use crate::{
     error::{Error, Result},
-    obj::store,
+    repository::Repository,
+    revision::{self, peel},
 };
 
 pub struct LsTree {
+    /// Revision naming a tree, or a commit or tag that peels to one.
     tree_sha: String,
 }
 
 impl LsTree {
     pub fn parse(args: &[String]) -> Result<Self> {
-        match args.get(0).map(|a| a.as_str()) {
+        match args.first().map(|a| a.as_str()) {
             Some("--name-only") => {}
             _ => {
                 return Err(Error::ParseCommand(String::from(
@@ -26,8 +28,9 @@ impl LsTree {
         }
     }
 
-    pub fn exec(self) -> Result<()> {
-        let object = store::read(&self.tree_sha)?;
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        let tree_sha = peel(repo, revision::resolve(repo, &self.tree_sha)?, "tree")?;
+        let object = repo.odb().read(&tree_sha)?;
 
         let tree = object
             .as_tree()
@@ -0,0 +1,76 @@
+use std::io::{self, Read};
+
+use crate::{
+    obj::{id::ObjectId, store::ObjectDatabase, tag::Tag, Object},
+    repository::Repository,
+    Error, Result,
+};
+
+pub struct MkTag;
+
+impl MkTag {
+    pub fn inner(odb: &dyn ObjectDatabase, input: &[u8]) -> Result<ObjectId> {
+        let tag = Tag::parse(input)?;
+
+        // Reading tags tolerates a malformed tagger, but new ones must have a valid one.
+        tag.tagger().transpose()?;
+
+        let target = odb.read_header(tag.object_sha())?;
+
+        if target.kind != tag.target_kind() {
+            return Err(Error::Generic(format!(
+                "object {} tagged as {} but is a {}",
+                tag.object_sha(),
+                tag.target_kind(),
+                target.kind
+            )));
+        }
+
+        odb.write(&Object::from_tag(tag))
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        let mut input = Vec::<u8>::new();
+        io::stdin().read_to_end(&mut input)?;
+
+        let sha = MkTag::inner(repo.odb(), &input)?;
+
+        println!("{}", sha);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        obj::{blob::Blob, store::MemoryObjectDatabase},
+        sha::ObjectFormat,
+    };
+
+    #[test]
+    fn test_mktag() {
+        let odb = MemoryObjectDatabase::new(ObjectFormat::Sha1);
+        let blob = odb
+            .write(&Object::from_blob(Blob::new(b"hello\n")))
+            .unwrap();
+
+        let tag = |kind: &str, tagger: &str| {
+            format!(
+                "object {}\ntype {}\ntag v1\ntagger {}\n\nmessage\n",
+                blob, kind, tagger
+            )
+        };
+
+        let input = tag("blob", "T <t@example.com> 1700000000 +0000");
+        let id = MkTag::inner(&odb, input.as_bytes()).unwrap();
+        let written = odb.read(&id).unwrap();
+        assert_eq!(input.as_bytes(), written.as_tag().unwrap().to_bytes());
+
+        assert!(MkTag::inner(&odb, tag("commit", "T <t@example.com> 1 +0000").as_bytes()).is_err());
+        assert!(
+            MkTag::inner(&odb, tag("blob", "T <t@example.com> soon +0000").as_bytes()).is_err()
+        );
+    }
+}
@@ -1,16 +1,32 @@
+pub mod add;
 pub mod args;
 pub mod cat_file;
 pub mod commit_tree;
+pub mod config;
+pub mod for_each_ref;
 pub mod hash_object;
 pub mod init;
 pub mod ls_tree;
+pub mod mktag;
+pub mod reflog;
+pub mod rev_parse;
+pub mod rm;
+pub mod show_ref;
+pub mod symbolic_ref;
+pub mod tag;
+pub mod update_ref;
 pub mod write_tree;
 
 use self::{
-    cat_file::CatFile, commit_tree::CommitTree, hash_object::HashObject, init::Init,
-    ls_tree::LsTree, write_tree::WriteTree,
+    add::Add, cat_file::CatFile, commit_tree::CommitTree, config::ConfigCmd,
+    for_each_ref::ForEachRef, hash_object::HashObject, init::Init, ls_tree::LsTree, mktag::MkTag,
+    reflog::ReflogCmd, rev_parse::RevParse, rm::Rm, show_ref::ShowRef, symbolic_ref::SymbolicRef,
+    tag::TagCmd, update_ref::UpdateRef, write_tree::WriteTree,
+};
+use crate::{
+    error::{Error, Result},
+    repository::Repository,
 };
-use crate::error::{Error, Result};
 
 pub enum Command {
     Init(Init),
@@ -19,6 +35,17 @@ pub enum Command {
     LsTree(LsTree),
     WriteTree(WriteTree),
     CommitTree(CommitTree),
+    Tag(TagCmd),
+    MkTag(MkTag),
+    RevParse(RevParse),
+    UpdateRef(UpdateRef),
+    SymbolicRef(SymbolicRef),
+    ShowRef(ShowRef),
+    ForEachRef(ForEachRef),
+    Reflog(ReflogCmd),
+    Add(Add),
+    Rm(Rm),
+    Config(ConfigCmd),
 }
 
 impl Command {
@@ -29,12 +56,23 @@ impl Command {
         let args = &args[2..];
 
         let cmd = match command.as_str() {
-            "init" => Self::Init(Init),
+            "init" => Self::Init(Init::parse(args)?),
             "cat-file" => Self::CatFile(CatFile::parse(args)?),
             "hash-object" => Self::HashObject(HashObject::parse(args)?),
             "ls-tree" => Self::LsTree(LsTree::parse(args)?),
-            "write-tree" => Self::WriteTree(WriteTree),
+            "write-tree" => Self::WriteTree(WriteTree::parse(args)?),
             "commit-tree" => Self::CommitTree(CommitTree::parse(args)?),
+            "tag" => Self::Tag(TagCmd::parse(args)?),
+            "mktag" => Self::MkTag(MkTag),
+            "rev-parse" => Self::RevParse(RevParse::parse(args)?),
+            "update-ref" => Self::UpdateRef(UpdateRef::parse(args)?),
+            "symbolic-ref" => Self::SymbolicRef(SymbolicRef::parse(args)?),
+            "show-ref" => Self::ShowRef(ShowRef::parse(args)?),
+            "for-each-ref" => Self::ForEachRef(ForEachRef::parse(args)?),
+            "reflog" => Self::Reflog(ReflogCmd::parse(args)?),
+            "add" => Self::Add(Add::parse(args)?),
+            "rm" => Self::Rm(Rm::parse(args)?),
+            "config" => Self::Config(ConfigCmd::parse(args)?),
             _ => return Err(Error::ParseCommand(format!("unknown command: {}", command))),
         };
 
@@ -43,12 +81,38 @@ impl Command {
 
     pub fn exec(self) -> Result<()> {
         match self {
-            Self::Init(init) => init.exec(),
-            Self::CatFile(cat_file) => cat_file.exec(),
-            Self::HashObject(hash_object) => hash_object.exec(),
-            Self::LsTree(ls_tree) => ls_tree.exec(),
-            Self::WriteTree(write_tree) => write_tree.exec(),
-            Self::CommitTree(commit_tree) => commit_tree.exec(),
+            Self::Init(init) => return init.exec(),
+            Self::Config(config) => {
+                let repo = match Repository::discover() {
+                    Ok(repo) => Some(repo),
+                    Err(Error::NotARepository(_)) => None,
+                    Err(err) => return Err(err),
+                };
+
+                return config.exec(repo.as_ref());
+            }
+            _ => {}
+        }
+
+        let repo = Repository::discover()?;
+
+        match self {
+            Self::CatFile(cat_file) => cat_file.exec(&repo),
+            Self::HashObject(hash_object) => hash_object.exec(&repo),
+            Self::LsTree(ls_tree) => ls_tree.exec(&repo),
+            Self::WriteTree(write_tree) => write_tree.exec(&repo),
+            Self::CommitTree(commit_tree) => commit_tree.exec(&repo),
+            Self::Tag(tag) => tag.exec(&repo),
+            Self::MkTag(mktag) => mktag.exec(&repo),
+            Self::RevParse(rev_parse) => rev_parse.exec(&repo),
+            Self::UpdateRef(update_ref) => update_ref.exec(&repo),
+            Self::SymbolicRef(symbolic_ref) => symbolic_ref.exec(&repo),
+            Self::ShowRef(show_ref) => show_ref.exec(&repo),
+            Self::ForEachRef(for_each_ref) => for_each_ref.exec(&repo),
+            Self::Reflog(reflog) => reflog.exec(&repo),
+            Self::Add(add) => add.exec(&repo),
+            Self::Rm(rm) => rm.exec(&repo),
+            Self::Init(_) | Self::Config(_) => unreachable!(),
         }
     }
 }
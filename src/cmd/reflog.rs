@@ -0,0 +1,480 @@
+use std::collections::HashSet;
+
+use crate::{
+    error::{Error, Result},
+    obj::{
+        id::ObjectId,
+        signature::{approxidate, current_time},
+        Object,
+    },
+    refs::{
+        dwim_ref, list_refs, read_ref,
+        reflog::{list_reflogs, read_reflog, reflog_exists, write_reflog, ReflogEntry},
+        resolve_ref, write_ref, RefValue,
+    },
+    repository::Repository,
+};
+
+/// How long reflog entries are kept when neither `--expire` nor `gc.reflogExpire` is set.
+const DEFAULT_EXPIRE: &str = "90.days.ago";
+/// Like [`DEFAULT_EXPIRE`], for entries no longer reachable from the ref, overridden by
+/// `--expire-unreachable` and `gc.reflogExpireUnreachable`.
+const DEFAULT_EXPIRE_UNREACHABLE: &str = "30.days.ago";
+
+/// What to do with the remaining entries after `expire` or `delete` pruned some.
+#[derive(Default)]
+pub struct PruneOptions {
+    /// Only report, without changing the reflog.
+    dry_run: bool,
+    /// Make each entry's old id the new id of the entry before it, or null for the oldest.
+    rewrite: bool,
+    /// Point the ref at the newest remaining entry if the newest one was pruned. Like git,
+    /// symbolic refs such as `HEAD` on a branch are left alone.
+    update_ref: bool,
+}
+
+pub enum ReflogAction {
+    /// Lists the entries of a ref's reflog newest first, starting at the `@{<n>}` it names.
+    Show {
+        name: String,
+    },
+    Expire {
+        names: Vec<String>,
+        all: bool,
+        expire: Option<String>,
+        expire_unreachable: Option<String>,
+        options: PruneOptions,
+    },
+    /// Removes single `<ref>@{<n>}` entries, one after the other.
+    Delete {
+        entries: Vec<String>,
+        options: PruneOptions,
+    },
+    Exists {
+        name: String,
+    },
+}
+
+pub struct ReflogCmd {
+    action: ReflogAction,
+}
+
+impl ReflogCmd {
+    pub fn new(action: ReflogAction) -> Self {
+        Self { action }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let (subcommand, args) = match args.first().map(String::as_str) {
+            Some(sub @ ("show" | "expire" | "delete" | "exists")) => (sub, &args[1..]),
+            _ => ("show", args),
+        };
+
+        let mut options = PruneOptions::default();
+        let mut all = false;
+        let mut expire = None;
+        let mut expire_unreachable = None;
+        let mut positional = Vec::new();
+
+        for arg in args {
+            let prune = matches!(subcommand, "expire" | "delete");
+
+            match arg.as_str() {
+                "-n" | "--dry-run" if prune => options.dry_run = true,
+                "--rewrite" if prune => options.rewrite = true,
+                "--updateref" if prune => options.update_ref = true,
+                "--all" if subcommand == "expire" => all = true,
+                _ if subcommand == "expire" && arg.starts_with("--expire=") => {
+                    expire = Some(arg["--expire=".len()..].to_owned())
+                }
+                _ if subcommand == "expire" && arg.starts_with("--expire-unreachable=") => {
+                    expire_unreachable = Some(arg["--expire-unreachable=".len()..].to_owned())
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => positional.push(arg.to_owned()),
+            }
+        }
+
+        let single = |positional: Vec<String>, default: Option<&str>| {
+            let mut positional = positional.into_iter();
+            let name = positional
+                .next()
+                .or_else(|| default.map(String::from))
+                .ok_or_else(|| Error::ParseCommand(String::from("missing ref name arg")))?;
+
+            match positional.next() {
+                Some(arg) => Err(Error::ParseCommand(format!("unexpected arg: {}", arg))),
+                None => Ok(name),
+            }
+        };
+
+        let action = match subcommand {
+            "show" => ReflogAction::Show {
+                name: single(positional, Some("HEAD"))?,
+            },
+            "exists" => ReflogAction::Exists {
+                name: single(positional, None)?,
+            },
+            "expire" => ReflogAction::Expire {
+                names: positional,
+                all,
+                expire,
+                expire_unreachable,
+                options,
+            },
+            _ if positional.is_empty() => {
+                return Err(Error::ParseCommand(String::from(
+                    "no reflog specified to delete",
+                )))
+            }
+            _ => ReflogAction::Delete {
+                entries: positional,
+                options,
+            },
+        };
+
+        Ok(Self::new(action))
+    }
+
+    pub fn inner(&self, repo: &Repository) -> Result<Vec<String>> {
+        match &self.action {
+            ReflogAction::Show { name } => show(repo, name),
+            ReflogAction::Exists { name } => match reflog_exists(repo.git_dir(), name) {
+                true => Ok(Vec::new()),
+                false => Err(Error::Generic(format!(
+                    "reflog for '{}' does not exist",
+                    name
+                ))),
+            },
+            ReflogAction::Expire {
+                names,
+                all,
+                expire,
+                expire_unreachable,
+                options,
+            } => {
+                let now = current_time();
+                let expire = expire_time(repo, expire, "gc.reflogExpire", DEFAULT_EXPIRE, now)?;
+                let expire_unreachable = expire_time(
+                    repo,
+                    expire_unreachable,
+                    "gc.reflogExpireUnreachable",
+                    DEFAULT_EXPIRE_UNREACHABLE,
+                    now,
+                )?;
+
+                let names = match all {
+                    true => list_reflogs(repo.git_dir())?,
+                    false => names
+                        .iter()
+                        .map(|name| reflog_name(repo, name))
+                        .collect::<Result<_>>()?,
+                };
+
+                for name in names {
+                    expire_reflog(repo, &name, expire, expire_unreachable, options)?;
+                }
+
+                Ok(Vec::new())
+            }
+            ReflogAction::Delete { entries, options } => {
+                for spec in entries {
+                    delete_entry(repo, spec, options)?;
+                }
+
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        for line in self.inner(repo)? {
+            println!("{}", line);
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits `<ref>@{<n>}` into the ref and the index of the entry, counting from the newest.
+fn parse_selector(spec: &str) -> Option<(&str, usize)> {
+    let (name, selector) = spec.strip_suffix('}')?.split_once("@{")?;
+
+    Some((name, selector.parse().ok()?))
+}
+
+/// Expands a short ref name to the full name of the ref whose reflog is meant.
+fn reflog_name(repo: &Repository, name: &str) -> Result<String> {
+    match name {
+        "" | "@" | "HEAD" => Ok(String::from("HEAD")),
+        name => match dwim_ref(repo.git_dir(), name)? {
+            Some((full_name, _)) => Ok(full_name),
+            None if reflog_exists(repo.git_dir(), name) => Ok(name.to_owned()),
+            None => Err(Error::UnknownRevision(name.to_owned())),
+        },
+    }
+}
+
+fn show(repo: &Repository, spec: &str) -> Result<Vec<String>> {
+    let (name, start) = parse_selector(spec).unwrap_or((spec, 0));
+    let display = match name {
+        "" | "@" => "HEAD",
+        name => name,
+    };
+
+    let entries = read_reflog(repo.git_dir(), &reflog_name(repo, name)?)?;
+    let abbrev_len = repo.abbrev_len()?;
+
+    entries
+        .iter()
+        .rev()
+        .enumerate()
+        .skip(start)
+        .map(|(i, entry)| {
+            Ok(format!(
+                "{} {}@{{{}}}: {}",
+                repo.odb().shortest_unique_prefix(&entry.new, abbrev_len)?,
+                display,
+                i,
+                entry.message
+            ))
+        })
+        .collect()
+}
+
+/// Reads an expiry time from the command line or `key`, where `never` keeps every entry and
+/// `all` drops every entry.
+fn expire_time(
+    repo: &Repository,
+    value: &Option<String>,
+    key: &str,
+    default: &str,
+    now: i64,
+) -> Result<Option<i64>> {
+    let value = match value {
+        Some(value) => value.as_str(),
+        None => repo.config().get(key).unwrap_or(default),
+    };
+
+    match value {
+        "never" | "false" => Ok(None),
+        "all" => Ok(Some(i64::MAX)),
+        value => approxidate(value, now)
+            .map(Some)
+            .ok_or_else(|| Error::InvalidDate(value.to_owned())),
+    }
+}
+
+fn expire_reflog(
+    repo: &Repository,
+    name: &str,
+    expire: Option<i64>,
+    expire_unreachable: Option<i64>,
+    options: &PruneOptions,
+) -> Result<()> {
+    let entries = read_reflog(repo.git_dir(), name)?;
+    let is_older = |entry: &ReflogEntry, time: Option<i64>| {
+        time.is_some_and(|time| entry.committer.time < time)
+    };
+
+    // Only walk history when some entry is old enough for reachability to matter.
+    let reachable = match entries
+        .iter()
+        .any(|e| !is_older(e, expire) && is_older(e, expire_unreachable))
+    {
+        true => {
+            let tips = match name {
+                // Entries of `HEAD` may come from any branch that was checked out.
+                "HEAD" => list_refs(repo.git_dir(), "")?
+                    .into_iter()
+                    .map(|r| r.id)
+                    .chain(resolve_ref(repo.git_dir(), "HEAD")?.1)
+                    .collect(),
+                name => Vec::from_iter(resolve_ref(repo.git_dir(), name)?.1),
+            };
+
+            reachable_from(repo, tips)?
+        }
+        false => HashSet::new(),
+    };
+
+    let kept = entries
+        .iter()
+        .filter(|e| {
+            let unreachable = || {
+                [e.old, e.new]
+                    .iter()
+                    .any(|id| !id.is_null() && !reachable.contains(id))
+            };
+            let expired = is_older(e, expire) || (is_older(e, expire_unreachable) && unreachable());
+
+            !expired
+        })
+        .cloned()
+        .collect();
+
+    prune(repo, name, &entries, kept, options)
+}
+
+fn delete_entry(repo: &Repository, spec: &str, options: &PruneOptions) -> Result<()> {
+    let (name, n) =
+        parse_selector(spec).ok_or_else(|| Error::Generic(format!("not a reflog: {}", spec)))?;
+    let name = reflog_name(repo, name)?;
+
+    let entries = read_reflog(repo.git_dir(), &name)?;
+
+    // Like git, entries past the end of the log are silently ignored.
+    let index = match entries.len().checked_sub(n + 1) {
+        Some(index) => index,
+        None => return Ok(()),
+    };
+
+    let mut kept = entries.clone();
+    kept.remove(index);
+
+    prune(repo, &name, &entries, kept, options)
+}
+
+/// Replaces the reflog of `name` with the `kept` subset of its `entries`.
+fn prune(
+    repo: &Repository,
+    name: &str,
+    entries: &[ReflogEntry],
+    mut kept: Vec<ReflogEntry>,
+    options: &PruneOptions,
+) -> Result<()> {
+    if options.dry_run || kept.len() == entries.len() {
+        return Ok(());
+    }
+
+    if options.rewrite {
+        let mut previous = ObjectId::null(repo.object_format());
+
+        for entry in &mut kept {
+            entry.old = previous;
+            previous = entry.new;
+        }
+    }
+
+    write_reflog(repo.git_dir(), name, &kept)?;
+
+    match (kept.last(), entries.last()) {
+        (Some(top), Some(old_top)) if options.update_ref && top != old_top => {
+            match read_ref(repo.git_dir(), name)? {
+                Some(RefValue::Symbolic(_)) => Ok(()),
+                _ => write_ref(repo.git_dir(), name, &top.new, None),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Collects every object reachable from `tips` through commit parents and tag targets.
+fn reachable_from(repo: &Repository, tips: Vec<ObjectId>) -> Result<HashSet<ObjectId>> {
+    let mut reachable = HashSet::new();
+    let mut pending = tips;
+
+    while let Some(id) = pending.pop() {
+        // Entries pointing at missing objects are unreachable, not an error.
+        if reachable.contains(&id) || !repo.odb().contains(&id) {
+            continue;
+        }
+
+        match repo.odb().read(&id)? {
+            Object::Commit(commit) => pending.extend_from_slice(commit.parent_shas()),
+            Object::Tag(tag) => pending.push(*tag.object_sha()),
+            _ => {}
+        }
+
+        reachable.insert(id);
+    }
+
+    Ok(reachable)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{
+        obj::{blob::Blob, signature::Signature},
+        refs::{
+            reflog::{append_reflog, LogRefUpdates, ReflogMessage},
+            write_symbolic_ref,
+        },
+        sha::ObjectFormat,
+    };
+
+    fn run(repo: &Repository, args: &[&str]) -> Result<Vec<String>> {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        ReflogCmd::parse(&args)?.inner(repo)
+    }
+
+    #[test]
+    fn test_show_expire_and_delete() {
+        let work_tree = env::temp_dir().join(format!("reflog-cmd-test-{}", process::id()));
+        let repo = Repository::init(&work_tree, ObjectFormat::Sha1).unwrap();
+        let git_dir = repo.git_dir();
+
+        let ids = ["a", "b", "c"].map(|content| {
+            repo.odb()
+                .write(&Object::from_blob(Blob::new(content.as_bytes())))
+                .unwrap()
+        });
+        let now = current_time();
+
+        write_symbolic_ref(git_dir, "HEAD", "refs/heads/main", None).unwrap();
+        write_ref(git_dir, "refs/heads/main", &ids[2], None).unwrap();
+
+        let mut old = ObjectId::null(ObjectFormat::Sha1);
+        for (id, days) in ids.iter().zip([10, 5, 1]) {
+            let committer = Signature::new("A", "a@b", now - days * 86400, 0);
+            let reflog = ReflogMessage::new(committer, "moved", LogRefUpdates::Branches);
+
+            for name in ["HEAD", "refs/heads/main"] {
+                append_reflog(git_dir, name, old, *id, &reflog).unwrap();
+            }
+            old = *id;
+        }
+
+        let shown = run(&repo, &["show", "main@{1}"]).unwrap();
+        assert_eq!(2, shown.len());
+        assert!(shown[0].ends_with(" main@{1}: moved"));
+        assert_eq!(3, run(&repo, &[]).unwrap().len());
+
+        run(&repo, &["delete", "--updateref", "HEAD@{0}"]).unwrap();
+        assert_eq!(2, read_reflog(git_dir, "HEAD").unwrap().len());
+        assert_eq!(
+            Some(RefValue::Symbolic(String::from("refs/heads/main"))),
+            read_ref(git_dir, "HEAD").unwrap()
+        );
+
+        run(&repo, &["delete", "--updateref", "--rewrite", "main@{0}"]).unwrap();
+        assert_eq!(Some(ids[1]), resolve_ref(git_dir, "HEAD").unwrap().1);
+
+        run(
+            &repo,
+            &[
+                "expire",
+                "--expire=7.days.ago",
+                "--expire-unreachable=never",
+                "main",
+            ],
+        )
+        .unwrap();
+        let entries = read_reflog(git_dir, "refs/heads/main").unwrap();
+        assert_eq!(
+            vec![ids[1]],
+            entries.iter().map(|e| e.new).collect::<Vec<_>>()
+        );
+
+        assert!(run(&repo, &["delete", "main"]).is_err());
+        assert!(run(&repo, &["exists", "refs/heads/other"]).is_err());
+
+        fs::remove_dir_all(work_tree).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{
+    error::{Error, Result},
+    repository::Repository,
+    revision,
+};
+
+pub struct RevParse {
+    revisions: Vec<String>,
+    verify: bool,
+    /// `--short[=<n>]`, with `Some(None)` meaning the configured length.
+    short: Option<Option<usize>>,
+}
+
+impl RevParse {
+    pub fn new(revisions: Vec<String>, verify: bool, short: Option<Option<usize>>) -> Self {
+        Self {
+            revisions,
+            verify,
+            short,
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut revisions = Vec::new();
+        let mut verify = false;
+        let mut short = None;
+
+        for arg in args {
+            match arg.as_str() {
+                "--verify" => verify = true,
+                "--short" => short = Some(None),
+                _ if arg.starts_with("--short=") => {
+                    let len = arg["--short=".len()..].parse().map_err(|_| {
+                        Error::ParseCommand(format!("invalid abbreviation length: {}", arg))
+                    })?;
+                    short = Some(Some(len));
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => revisions.push(arg.to_owned()),
+            }
+        }
+
+        Ok(Self::new(revisions, verify, short))
+    }
+
+    pub fn inner(&self, repo: &Repository) -> Result<Vec<String>> {
+        if (self.verify || self.short.is_some()) && self.revisions.len() != 1 {
+            return Err(Error::Generic(String::from("needed a single revision")));
+        }
+
+        let min_len = match self.short {
+            Some(Some(len)) => Some(len),
+            Some(None) => Some(repo.abbrev_len()?),
+            None => None,
+        };
+
+        self.revisions
+            .iter()
+            .map(|rev| {
+                let id = revision::resolve(repo, rev)?;
+
+                match min_len {
+                    Some(len) => repo.odb().shortest_unique_prefix(&id, len),
+                    None => Ok(id.to_string()),
+                }
+            })
+            .collect()
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        for line in self.inner(repo)? {
+            println!("{}", line);
+        }
+
+        Ok(())
+    }
+}
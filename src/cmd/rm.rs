@@ -0,0 +1,250 @@
+use std::{env, fs, io, path::Path};
+
+use super::add::hash_file;
+use crate::{
+    error::{Error, Result},
+    index::{entry::IndexEntry, Index},
+    obj::store::MemoryObjectDatabase,
+    pathspec::{Pathspec, PathspecMatch},
+    repository::Repository,
+    revision,
+};
+
+pub struct Rm {
+    paths: Vec<String>,
+    /// Only remove the entries from the index, keeping the files.
+    cached: bool,
+    recursive: bool,
+    /// Skip the checks that refuse to lose staged or local changes.
+    force: bool,
+    dry_run: bool,
+    quiet: bool,
+    ignore_unmatch: bool,
+}
+
+impl Rm {
+    pub fn new(paths: Vec<String>, cached: bool, recursive: bool, force: bool) -> Self {
+        Self {
+            paths,
+            cached,
+            recursive,
+            force,
+            dry_run: false,
+            quiet: false,
+            ignore_unmatch: false,
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut rm = Self::new(Vec::new(), false, false, false);
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--cached" => rm.cached = true,
+                "-r" => rm.recursive = true,
+                "-f" | "--force" => rm.force = true,
+                "-n" | "--dry-run" => rm.dry_run = true,
+                "-q" | "--quiet" => rm.quiet = true,
+                "--ignore-unmatch" => rm.ignore_unmatch = true,
+                "--" => rm.paths.extend(args.by_ref().cloned()),
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => rm.paths.push(arg.to_owned()),
+            }
+        }
+
+        if rm.paths.is_empty() {
+            return Err(Error::ParseCommand(String::from("no pathspec given")));
+        }
+
+        Ok(rm)
+    }
+
+    /// Removes the matching entries, and their files unless `--cached`, returning an
+    /// `rm '<path>'` line for each.
+    pub fn inner(&self, repo: &Repository, cwd: &Path) -> Result<Vec<String>> {
+        let work_tree = repo.require_work_tree()?;
+        let pathspec = Pathspec::new(work_tree, cwd, &self.paths)?;
+        let mut index = repo.index()?;
+        let mut matched = vec![self.ignore_unmatch; pathspec.items().len()];
+        let mut paths = Vec::<String>::new();
+
+        for entry in index.entries() {
+            if paths.last() == Some(&entry.path) {
+                continue;
+            }
+
+            let mut matches = false;
+
+            for (item, matched) in pathspec.items().iter().zip(&mut matched) {
+                match item.matches(&entry.path) {
+                    Some(PathspecMatch::Leading) if !self.recursive => {
+                        return Err(Error::Generic(format!(
+                            "not removing '{}' recursively without -r",
+                            item.arg
+                        )))
+                    }
+                    Some(_) => {
+                        *matched = true;
+                        matches = true;
+                    }
+                    None => {}
+                }
+            }
+
+            if matches {
+                paths.push(entry.path.clone());
+            }
+        }
+
+        pathspec.check_matched(&matched)?;
+
+        if !self.force {
+            self.check_local_changes(repo, &index, &paths)?;
+        }
+
+        if self.dry_run {
+            return Ok(paths.iter().map(|p| format!("rm '{}'", p)).collect());
+        }
+
+        for path in &paths {
+            index.remove(path);
+        }
+
+        index.write(&repo.index_path())?;
+
+        if !self.cached {
+            for path in &paths {
+                remove_file(work_tree, path)?;
+            }
+        }
+
+        Ok(paths.iter().map(|p| format!("rm '{}'", p)).collect())
+    }
+
+    /// Refuses to remove entries whose content would be lost: entries that differ from both
+    /// `HEAD` and the work tree, and unless `--cached`, entries that differ from either.
+    fn check_local_changes(
+        &self,
+        repo: &Repository,
+        index: &Index,
+        paths: &[String],
+    ) -> Result<()> {
+        let work_tree = repo.require_work_tree()?;
+        let odb = MemoryObjectDatabase::new(repo.object_format());
+        let mut both = Vec::new();
+        let mut staged = Vec::new();
+        let mut local = Vec::new();
+
+        for path in paths {
+            // Unmerged entries have nothing worth keeping.
+            let entry = match index.entry(path, 0) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            let staged_changes = revision::resolve(repo, &format!("HEAD:{}", path))
+                .map_or(true, |id| id != entry.id);
+            let local_changes = is_modified(index, entry, &work_tree.join(path), &odb)?;
+
+            match (staged_changes, local_changes) {
+                (true, true) => both.push(path.as_str()),
+                (true, false) if !self.cached => staged.push(path.as_str()),
+                (false, true) if !self.cached => local.push(path.as_str()),
+                _ => {}
+            }
+        }
+
+        let mut errors = Vec::new();
+        let mut report = |paths: &[&str], problem: &str, hint: &str| {
+            if paths.is_empty() {
+                return;
+            }
+
+            let subject = match paths.len() {
+                1 => "file has",
+                _ => "files have",
+            };
+            let list = paths
+                .iter()
+                .map(|p| format!("    {}\n", p))
+                .collect::<String>();
+
+            errors.push(format!(
+                "the following {} {}:\n{}{}",
+                subject, problem, list, hint
+            ));
+        };
+
+        let keep_hint = "(use --cached to keep the file, or -f to force removal)";
+        report(
+            &both,
+            "staged content different from both the\nfile and the HEAD",
+            "(use -f to force removal)",
+        );
+        report(&staged, "changes staged in the index", keep_hint);
+        report(&local, "local modifications", keep_hint);
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Generic(errors.join("\n"))),
+        }
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        let output = self.inner(repo, &env::current_dir()?)?;
+
+        if !self.quiet {
+            for line in output {
+                println!("{}", line);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the work tree file at `path` has content other than `entry`. A missing file has
+/// nothing to lose.
+fn is_modified(
+    index: &Index,
+    entry: &IndexEntry,
+    path: &Path,
+    odb: &MemoryObjectDatabase,
+) -> Result<bool> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.is_dir() => metadata,
+        Ok(_) => return Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    if index.is_up_to_date(entry, &metadata) {
+        return Ok(false);
+    }
+
+    Ok(hash_file(odb, path, &metadata)? != entry.id)
+}
+
+/// Deletes the file of a removed entry, then the directories it leaves empty.
+fn remove_file(work_tree: &Path, path: &str) -> Result<()> {
+    match fs::remove_file(work_tree.join(path)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut parent = path;
+
+    while let Some((dir, _)) = parent.rsplit_once('/') {
+        if fs::remove_dir(work_tree.join(dir)).is_err() {
+            break;
+        }
+
+        parent = dir;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use crate::{
+    error::{Error, Result},
+    obj::id::ObjectId,
+    refs::{list_refs, resolve_ref, Reference},
+    repository::Repository,
+    revision::peel_tags,
+};
+
+#[derive(Default)]
+pub struct ShowRef {
+    patterns: Vec<String>,
+    head: bool,
+    heads: bool,
+    tags: bool,
+    /// Also show what annotated tags peel to, as `<id> <name>^{}`.
+    dereference: bool,
+    /// Only print ids, abbreviated to the given length when set.
+    hash: Option<Option<usize>>,
+    /// Treat patterns as exact ref names that must all exist.
+    verify: bool,
+}
+
+impl ShowRef {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut show_ref = Self::default();
+
+        for arg in args {
+            match arg.as_str() {
+                "--head" => show_ref.head = true,
+                "--heads" => show_ref.heads = true,
+                "--tags" => show_ref.tags = true,
+                "-d" | "--dereference" => show_ref.dereference = true,
+                "-s" | "--hash" => show_ref.hash = Some(None),
+                "--verify" => show_ref.verify = true,
+                _ if arg.starts_with("--hash=") => {
+                    let len = arg["--hash=".len()..].parse().map_err(|_| {
+                        Error::ParseCommand(format!("invalid abbreviation length: {}", arg))
+                    })?;
+                    show_ref.hash = Some(Some(len));
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => show_ref.patterns.push(arg.to_owned()),
+            }
+        }
+
+        Ok(show_ref)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let kind_matches = match (self.heads, self.tags) {
+            (false, false) => true,
+            (heads, tags) => {
+                (heads && name.starts_with("refs/heads/"))
+                    || (tags && name.starts_with("refs/tags/"))
+            }
+        };
+
+        kind_matches
+            && (self.patterns.is_empty()
+                || self.patterns.iter().any(|pattern| {
+                    name == pattern
+                        || name
+                            .strip_suffix(pattern.as_str())
+                            .is_some_and(|rest| rest.ends_with('/'))
+                }))
+    }
+
+    fn refs(&self, repo: &Repository) -> Result<Vec<Reference>> {
+        let git_dir = repo.git_dir();
+
+        if self.verify {
+            if self.patterns.is_empty() {
+                return Err(Error::ParseCommand(String::from(
+                    "--verify requires a ref name",
+                )));
+            }
+
+            return self
+                .patterns
+                .iter()
+                .map(|name| {
+                    let id = match name.starts_with("refs/") || name == "HEAD" {
+                        true => resolve_ref(git_dir, name)?.1,
+                        false => None,
+                    };
+
+                    id.map(|id| Reference {
+                        name: name.to_owned(),
+                        id,
+                        peeled: None,
+                    })
+                    .ok_or_else(|| Error::Generic(format!("'{}' - not a valid ref", name)))
+                })
+                .collect();
+        }
+
+        let mut refs = Vec::new();
+
+        if self.head {
+            if let (_, Some(id)) = resolve_ref(git_dir, "HEAD")? {
+                refs.push(Reference {
+                    name: String::from("HEAD"),
+                    id,
+                    peeled: None,
+                });
+            }
+        }
+
+        refs.extend(
+            list_refs(git_dir, "refs/")?
+                .into_iter()
+                .filter(|r| self.matches(&r.name)),
+        );
+
+        Ok(refs)
+    }
+
+    fn format_id(&self, repo: &Repository, id: &ObjectId) -> Result<String> {
+        match self.hash {
+            Some(Some(len)) => repo.odb().shortest_unique_prefix(id, len),
+            _ => Ok(id.to_string()),
+        }
+    }
+
+    pub fn inner(&self, repo: &Repository) -> Result<Vec<String>> {
+        let refs = self.refs(repo)?;
+
+        if refs.is_empty() {
+            return Err(Error::Generic(String::from("no matching refs")));
+        }
+
+        let mut lines = Vec::new();
+
+        for reference in refs {
+            let id = self.format_id(repo, &reference.id)?;
+
+            lines.push(match self.hash {
+                Some(_) => id,
+                None => format!("{} {}", id, reference.name),
+            });
+
+            if !self.dereference {
+                continue;
+            }
+
+            let peeled = match reference.peeled {
+                Some(peeled) => peeled,
+                None if repo.odb().read_header(&reference.id)?.kind == "tag" => {
+                    peel_tags(repo, reference.id)?
+                }
+                None => continue,
+            };
+            let peeled = self.format_id(repo, &peeled)?;
+
+            lines.push(match self.hash {
+                Some(_) => peeled,
+                None => format!("{} {}^{{}}", peeled, reference.name),
+            });
+        }
+
+        Ok(lines)
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        for line in self.inner(repo)? {
+            println!("{}", line);
+        }
+
+        Ok(())
+    }
+}
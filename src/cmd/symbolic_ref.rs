@@ -0,0 +1,115 @@
+use super::args::get_named_arg;
+use crate::{
+    error::{Error, Result},
+    refs::{delete_ref, read_ref, shorten_ref_name, write_symbolic_ref, RefValue},
+    repository::Repository,
+};
+
+pub enum SymbolicRefAction {
+    Read { short: bool },
+    Write { target: String },
+    Delete,
+}
+
+pub struct SymbolicRef {
+    name: String,
+    action: SymbolicRefAction,
+    /// Reason recorded in the reflog when the target changes.
+    msg: Option<String>,
+}
+
+impl SymbolicRef {
+    pub fn new(name: impl ToString, action: SymbolicRefAction, msg: Option<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            action,
+            msg,
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let (args, msg) = get_named_arg(args, "-m");
+        let mut delete = false;
+        let mut short = false;
+        let mut positional = Vec::new();
+
+        for arg in &args {
+            match arg.as_str() {
+                "-d" | "--delete" => delete = true,
+                "--short" => short = true,
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => positional.push(arg.to_owned()),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let name = positional
+            .next()
+            .ok_or_else(|| Error::ParseCommand(String::from("missing ref name arg")))?;
+
+        let action = match (delete, positional.next()) {
+            (true, None) => SymbolicRefAction::Delete,
+            (false, None) => SymbolicRefAction::Read { short },
+            (false, Some(target)) => SymbolicRefAction::Write { target },
+            (true, Some(arg)) => {
+                return Err(Error::ParseCommand(format!("unexpected arg: {}", arg)))
+            }
+        };
+
+        if let Some(arg) = positional.next() {
+            return Err(Error::ParseCommand(format!("unexpected arg: {}", arg)));
+        }
+
+        Ok(Self::new(name, action, msg))
+    }
+
+    pub fn inner(&self, repo: &Repository) -> Result<Option<String>> {
+        let git_dir = repo.git_dir();
+
+        let target = match read_ref(git_dir, &self.name)? {
+            Some(RefValue::Symbolic(target)) => Some(target),
+            _ => None,
+        };
+        let not_symbolic = || Error::Generic(format!("ref {} is not a symbolic ref", self.name));
+
+        match &self.action {
+            SymbolicRefAction::Read { short } => {
+                let target = target.ok_or_else(not_symbolic)?;
+
+                Ok(Some(match short {
+                    true => shorten_ref_name(&target).to_owned(),
+                    false => target,
+                }))
+            }
+            SymbolicRefAction::Write { target } => {
+                if self.name == "HEAD" && !target.starts_with("refs/") {
+                    return Err(Error::Generic(format!(
+                        "refusing to point HEAD outside of refs/: {}",
+                        target
+                    )));
+                }
+
+                let reflog = repo.reflog_message(self.msg.as_deref().unwrap_or_default())?;
+                write_symbolic_ref(git_dir, &self.name, target, Some(&reflog))?;
+
+                Ok(None)
+            }
+            SymbolicRefAction::Delete => {
+                target.ok_or_else(not_symbolic)?;
+                delete_ref(git_dir, &self.name)?;
+
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        if let Some(line) = self.inner(repo)? {
+            println!("{}", line);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use super::args::get_named_arg;
+use crate::{
+    obj::{
+        id::ObjectId,
+        signature::format_short_date,
+        tag::Tag,
+        user::{Role, User},
+        Object,
+    },
+    refs::{check_ref_format, resolve_ref, write_ref},
+    repository::Repository,
+    revision, Error, Result,
+};
+
+pub struct TagCmd {
+    name: String,
+    /// Revision naming the object to tag.
+    object: String,
+    msg: Option<String>,
+    force: bool,
+}
+
+impl TagCmd {
+    pub fn new(
+        name: impl ToString,
+        object: impl ToString,
+        msg: Option<impl ToString>,
+        force: bool,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            object: object.to_string(),
+            msg: msg.map(|m| m.to_string()),
+            force,
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let (args, msg) = get_named_arg(args, "-m");
+
+        let force = args.iter().any(|a| a == "-f");
+        let annotate = args.iter().any(|a| a == "-a");
+        let args = args
+            .into_iter()
+            .filter(|a| a != "-f" && a != "-a")
+            .collect::<Vec<_>>();
+
+        if annotate && msg.is_none() {
+            return Err(Error::ParseCommand(String::from(
+                "annotated tag requires a message, use \"-m\"",
+            )));
+        }
+
+        let name = args
+            .first()
+            .ok_or_else(|| Error::ParseCommand(String::from("missing tag name arg")))?;
+        let object = args
+            .get(1)
+            .ok_or_else(|| Error::ParseCommand(String::from("missing object sha arg")))?;
+
+        Ok(Self::new(name, object, msg, force))
+    }
+
+    pub fn inner(&self, repo: &Repository) -> Result<ObjectId> {
+        let odb = repo.odb();
+        let ref_name = format!("refs/tags/{}", self.name);
+        check_ref_format(&ref_name, false)?;
+
+        if resolve_ref(repo.git_dir(), &ref_name)?.1.is_some() && !self.force {
+            return Err(Error::Generic(format!(
+                "tag '{}' already exists",
+                self.name
+            )));
+        }
+
+        let object_sha = revision::resolve(repo, &self.object)?;
+        let target = odb.read_header(&object_sha)?;
+
+        let sha = match &self.msg {
+            Some(msg) => {
+                let tag = Tag::new(
+                    object_sha,
+                    target.kind,
+                    &self.name,
+                    msg,
+                    User::signature(Role::Committer, repo.config())?,
+                );
+
+                odb.write(&Object::from_tag(tag))?
+            }
+            None => object_sha,
+        };
+
+        let reflog = repo.reflog_message(&reflog_message(repo, &object_sha)?)?;
+        write_ref(repo.git_dir(), &ref_name, &sha, Some(reflog))?;
+
+        Ok(sha)
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        self.inner(repo)?;
+
+        Ok(())
+    }
+}
+
+/// Describes the tagged object for the reflog like git does, with the subject and date of a
+/// commit or the kind of any other object.
+fn reflog_message(repo: &Repository, id: &ObjectId) -> Result<String> {
+    let object = repo.odb().read(id)?;
+
+    let description = match object.as_commit() {
+        Some(commit) => format!(
+            "{}, {}",
+            commit.message().lines().next().unwrap_or_default(),
+            format_short_date(commit.committer().map_or(0, |c| c.time))
+        ),
+        None if object.is_tag() => String::from("other tag object"),
+        None => format!("{} object", object.kind()),
+    };
+
+    Ok(format!(
+        "tag: tagging {} ({})",
+        repo.odb().shortest_unique_prefix(id, repo.abbrev_len()?)?,
+        description
+    ))
+}
@@ -0,0 +1,357 @@
+use std::io::{self, Read};
+
+use super::args::get_named_arg;
+use crate::{
+    error::{Error, Result},
+    obj::id::ObjectId,
+    refs::{
+        reflog::LogRefUpdates,
+        resolve_ref,
+        transaction::{PreparedTransaction, RefUpdate, Transaction},
+    },
+    repository::Repository,
+    revision,
+};
+
+pub enum UpdateRefAction {
+    /// Points the ref at `new`, deleting it if `new` is the null id.
+    Update {
+        new: String,
+        old: Option<String>,
+    },
+    Delete {
+        old: Option<String>,
+    },
+    /// Reads `start`/`update`/`create`/`delete`/`verify`/`prepare`/`commit`/`abort` lines from
+    /// stdin and applies them in transactions.
+    Stdin,
+}
+
+pub struct UpdateRef {
+    name: String,
+    action: UpdateRefAction,
+    /// Update a symbolic ref itself instead of the ref it points to.
+    no_deref: bool,
+    /// Reason recorded in the reflog of the updated refs.
+    msg: Option<String>,
+    /// Create a reflog for the updated refs even if `core.logAllRefUpdates` would not.
+    create_reflog: bool,
+}
+
+impl UpdateRef {
+    pub fn new(
+        name: impl ToString,
+        action: UpdateRefAction,
+        no_deref: bool,
+        msg: Option<String>,
+        create_reflog: bool,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            action,
+            no_deref,
+            msg,
+            create_reflog,
+        }
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let (args, msg) = get_named_arg(args, "-m");
+        let mut delete = false;
+        let mut stdin = false;
+        let mut no_deref = false;
+        let mut create_reflog = false;
+        let mut positional = Vec::new();
+
+        for arg in &args {
+            match arg.as_str() {
+                "-d" => delete = true,
+                "--stdin" => stdin = true,
+                "--no-deref" => no_deref = true,
+                "--create-reflog" => create_reflog = true,
+                _ if arg.starts_with('-') => {
+                    return Err(Error::ParseCommand(format!("unknown option: {}", arg)))
+                }
+                _ => positional.push(arg.to_owned()),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+
+        if stdin {
+            if delete {
+                return Err(Error::ParseCommand(String::from(
+                    "-d cannot be used with --stdin",
+                )));
+            }
+            if let Some(arg) = positional.next() {
+                return Err(Error::ParseCommand(format!("unexpected arg: {}", arg)));
+            }
+
+            return Ok(Self::new(
+                "",
+                UpdateRefAction::Stdin,
+                no_deref,
+                msg,
+                create_reflog,
+            ));
+        }
+
+        let name = positional
+            .next()
+            .ok_or_else(|| Error::ParseCommand(String::from("missing ref name arg")))?;
+
+        let action = if delete {
+            UpdateRefAction::Delete {
+                old: positional.next(),
+            }
+        } else {
+            UpdateRefAction::Update {
+                new: positional
+                    .next()
+                    .ok_or_else(|| Error::ParseCommand(String::from("missing new value arg")))?,
+                old: positional.next(),
+            }
+        };
+
+        if let Some(arg) = positional.next() {
+            return Err(Error::ParseCommand(format!("unexpected arg: {}", arg)));
+        }
+
+        Ok(Self::new(name, action, no_deref, msg, create_reflog))
+    }
+
+    /// Starts a transaction that logs its updates with the `-m` message.
+    fn transaction<'a>(&self, repo: &'a Repository) -> Result<Transaction<'a>> {
+        let mut transaction = Transaction::new(repo.git_dir());
+
+        let mut reflog = repo.reflog_message(self.msg.as_deref().unwrap_or_default())?;
+        if self.create_reflog {
+            reflog.create = LogRefUpdates::Always;
+        }
+        transaction.reflog(reflog);
+
+        Ok(transaction)
+    }
+
+    /// Resolves the ref to update, following symbolic refs unless `--no-deref` was given.
+    fn target_name(&self, repo: &Repository, name: &str) -> Result<String> {
+        match self.no_deref {
+            true => Ok(name.to_owned()),
+            false => Ok(resolve_ref(repo.git_dir(), name)?.0),
+        }
+    }
+
+    pub fn inner(&self, repo: &Repository) -> Result<()> {
+        let name = self.target_name(repo, &self.name)?;
+
+        let (new, old) = match &self.action {
+            UpdateRefAction::Update { new, old } => (resolve_new_value(repo, new)?, old),
+            UpdateRefAction::Delete { old } => (ObjectId::null(repo.object_format()), old),
+            UpdateRefAction::Stdin => {
+                return Err(Error::Generic(String::from("expected stdin input")))
+            }
+        };
+
+        let mut transaction = self.transaction(repo)?;
+        transaction.push(RefUpdate {
+            name,
+            new: Some(new),
+            old: old
+                .as_deref()
+                .map(|old| resolve_value(repo, old))
+                .transpose()?,
+        })?;
+
+        transaction.commit()
+    }
+
+    /// Runs the `--stdin` commands in `input`, returning the status lines to print. Updates
+    /// outside of an explicit `start` are committed together at the end of the input.
+    pub fn inner_stdin(&self, repo: &Repository, input: &str) -> Result<Vec<String>> {
+        let mut output = Vec::new();
+        let mut transaction = Some(self.transaction(repo)?);
+        let mut prepared: Option<PreparedTransaction> = None;
+
+        for line in input.lines().filter(|line| !line.is_empty()) {
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let args = rest
+                .split(' ')
+                .filter(|a| !a.is_empty())
+                .collect::<Vec<_>>();
+            let invalid = || Error::ParseCommand(format!("invalid update-ref command: {}", line));
+
+            let arg = |i: usize| args.get(i).copied().ok_or_else(invalid);
+            let value = |i: usize| resolve_new_value(repo, arg(i)?);
+            let old = |i: usize| match args.get(i) {
+                Some(old) => resolve_value(repo, old).map(Some),
+                None => Ok(None),
+            };
+            let null = ObjectId::null(repo.object_format());
+
+            if args.len() > 3 {
+                return Err(invalid());
+            }
+
+            match command {
+                "start" | "prepare" | "commit" | "abort" => {
+                    match (command, transaction.take(), prepared.take()) {
+                        ("start", Some(open), None) if open.is_empty() => {}
+                        ("prepare", Some(open), None) => prepared = Some(open.prepare()?),
+                        ("commit", Some(open), None) => open.commit()?,
+                        ("commit", None, Some(locked)) => locked.commit()?,
+                        ("abort", _, _) => {}
+                        _ => return Err(invalid()),
+                    }
+
+                    if prepared.is_none() {
+                        transaction = Some(self.transaction(repo)?);
+                    }
+                    output.push(format!("{}: ok", command));
+
+                    continue;
+                }
+                _ => {}
+            }
+
+            let open = transaction.as_mut().ok_or_else(|| {
+                Error::Generic(format!(
+                    "prepared transactions can only be closed: {}",
+                    line
+                ))
+            })?;
+            let name = self.target_name(repo, arg(0)?)?;
+
+            match (command, args.len()) {
+                ("update", 2 | 3) => open.update(name, value(1)?, old(2)?)?,
+                ("create", 2) => open.create(name, value(1)?)?,
+                ("delete", 1 | 2) => open.delete(name, old(1)?)?,
+                ("verify", 1 | 2) => open.verify(name, old(1)?.unwrap_or(null))?,
+                _ => return Err(invalid()),
+            };
+        }
+
+        match (transaction, prepared) {
+            (_, Some(_)) => Err(Error::Generic(String::from(
+                "prepared transaction was neither committed nor aborted",
+            ))),
+            (Some(open), None) if !open.is_empty() => open.commit().map(|_| output),
+            _ => Ok(output),
+        }
+    }
+
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        if let UpdateRefAction::Stdin = self.action {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+
+            for line in self.inner_stdin(repo, &input)? {
+                println!("{}", line);
+            }
+
+            return Ok(());
+        }
+
+        self.inner(repo)
+    }
+}
+
+/// Like [`resolve_value`], but refuses objects that are not in the database, so that refs never
+/// point at missing objects.
+fn resolve_new_value(repo: &Repository, value: &str) -> Result<ObjectId> {
+    let id = resolve_value(repo, value)?;
+
+    match id.is_null() || repo.odb().contains(&id) {
+        true => Ok(id),
+        false => Err(Error::Generic(format!(
+            "trying to write ref with nonexistent object {}",
+            id
+        ))),
+    }
+}
+
+/// Resolves a new or old value, where an empty value or all-zero id stands for "no ref".
+fn resolve_value(repo: &Repository, value: &str) -> Result<ObjectId> {
+    match value.parse::<ObjectId>() {
+        _ if value.is_empty() => Ok(ObjectId::null(repo.object_format())),
+        Ok(id) if id.is_null() => Ok(ObjectId::null(repo.object_format())),
+        _ => revision::resolve(repo, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{
+        obj::{blob::Blob, Object},
+        refs::read_ref,
+        sha::ObjectFormat,
+    };
+
+    #[test]
+    fn test_stdin_transactions() {
+        let work_tree = env::temp_dir().join(format!("update-ref-test-{}", process::id()));
+        let repo = Repository::init(&work_tree, ObjectFormat::Sha1).unwrap();
+        let git_dir = repo.git_dir();
+        let cmd = UpdateRef::new("", UpdateRefAction::Stdin, false, None, false);
+
+        let [a, b, c] = ["a", "b", "c"].map(|content| {
+            repo.odb()
+                .write(&Object::from_blob(Blob::new(content.as_bytes())))
+                .unwrap()
+        });
+        let current = |name: &str| resolve_ref(git_dir, name).unwrap().1;
+
+        let output = cmd
+            .inner_stdin(
+                &repo,
+                &format!(
+                    "start\nupdate refs/heads/a {a}\ncreate refs/heads/b {b}\nprepare\ncommit\n"
+                ),
+            )
+            .unwrap();
+        assert_eq!(vec!["start: ok", "prepare: ok", "commit: ok"], output);
+        assert_eq!(
+            (Some(a), Some(b)),
+            (current("refs/heads/a"), current("refs/heads/b"))
+        );
+
+        let output = cmd
+            .inner_stdin(&repo, "start\ndelete refs/heads/b\nabort\n")
+            .unwrap();
+        assert_eq!(vec!["start: ok", "abort: ok"], output);
+        assert_eq!(Some(b), current("refs/heads/b"));
+
+        // A stale old value fails the whole transaction.
+        let stale = format!("update refs/heads/b {c}\nupdate refs/heads/a {b} {c}\n");
+        assert!(cmd.inner_stdin(&repo, &stale).is_err());
+        assert!(cmd
+            .inner_stdin(&repo, &format!("create refs/heads/a {c}\n"))
+            .is_err());
+        assert_eq!(
+            (Some(a), Some(b)),
+            (current("refs/heads/a"), current("refs/heads/b"))
+        );
+
+        let unfinished =
+            format!("start\nverify refs/heads/a {a}\ndelete refs/heads/b {b}\nprepare\n");
+        assert!(cmd.inner_stdin(&repo, &unfinished).is_err());
+        assert!(!git_dir.join("refs/heads/b.lock").exists());
+
+        let output = cmd
+            .inner_stdin(
+                &repo,
+                &format!("verify refs/heads/a {a}\ndelete refs/heads/b {b}\n"),
+            )
+            .unwrap();
+        assert!(output.is_empty());
+        assert_eq!(None, read_ref(git_dir, "refs/heads/b").unwrap());
+
+        assert!(cmd.inner_stdin(&repo, "frobnicate refs/heads/a\n").is_err());
+        assert!(cmd.inner_stdin(&repo, "prepare\nstart\n").is_err());
+
+        fs::remove_dir_all(work_tree).unwrap();
+    }
+}
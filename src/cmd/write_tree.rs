@@ -1,56 +1,326 @@
 use std::{fs, path::Path};
 
-use super::hash_object::HashObject;
+use super::add::hash_file;
 use crate::{
+    index::{
+        entry::{mode_from_metadata, IndexEntry, MODE_GITLINK},
+        Index,
+    },
     obj::{
-        store,
+        id::ObjectId,
+        store::ObjectDatabase,
         tree::{Tree, TreeEntry, TreeEntryMode},
         Object,
     },
-    Result,
+    refs::resolve_ref,
+    repository::{nested_git_dir, Repository},
+    Error, Result,
 };
 
-pub struct WriteTree;
+pub struct WriteTree {
+    /// Hash every file in the work tree instead of writing the staged entries.
+    from_work_tree: bool,
+    /// Write entries whose objects are not in the database.
+    missing_ok: bool,
+    /// Write the tree of this directory instead of the top-level tree.
+    prefix: Option<String>,
+}
 
 impl WriteTree {
-    pub fn inner(root: impl AsRef<Path>) -> Result<String> {
-        let mut tree_entries = Vec::<TreeEntry>::new();
-
-        for entry in fs::read_dir(root)? {
-            let path = entry?.path();
+    pub fn new(from_work_tree: bool, missing_ok: bool, prefix: Option<String>) -> Self {
+        Self {
+            from_work_tree,
+            missing_ok,
+            prefix,
+        }
+    }
 
-            let name = path.file_name().unwrap().to_str().unwrap();
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut write_tree = Self::new(false, false, None);
 
-            if name == ".git" {
-                continue;
+        for arg in args {
+            match arg.as_str() {
+                "--from-work-tree" => write_tree.from_work_tree = true,
+                "--missing-ok" => write_tree.missing_ok = true,
+                _ if arg.starts_with("--prefix=") => {
+                    write_tree.prefix = Some(arg["--prefix=".len()..].to_owned())
+                }
+                _ => return Err(Error::ParseCommand(format!("unknown option: {}", arg))),
             }
+        }
+
+        if write_tree.from_work_tree && write_tree.prefix.is_some() {
+            return Err(Error::ParseCommand(String::from(
+                "--prefix cannot be used with --from-work-tree",
+            )));
+        }
 
-            let tree_entry = if path.is_dir() {
-                let sha = WriteTree::inner(&path)?;
+        Ok(write_tree)
+    }
+
+    /// Writes the trees for the staged entries of `index`, below `prefix` if given. Fails
+    /// when the index has unmerged entries, or, unless `missing_ok`, entries whose objects
+    /// are missing. Entries only added with intent to add are left out.
+    pub fn from_index(
+        odb: &dyn ObjectDatabase,
+        index: &Index,
+        prefix: Option<&str>,
+        missing_ok: bool,
+    ) -> Result<ObjectId> {
+        if let Some(entry) = index.entries().iter().find(|e| e.stage > 0) {
+            return Err(Error::Generic(format!(
+                "cannot write a tree: '{}' is unmerged",
+                entry.path
+            )));
+        }
 
-                TreeEntry::new(TreeEntryMode::Tree, name, sha)
-            } else {
-                let sha = HashObject::new(&path.to_string_lossy()).inner()?;
+        let prefix = match prefix.map(|p| p.trim_end_matches('/')) {
+            Some("") | None => String::new(),
+            Some(prefix) => format!("{}/", prefix),
+        };
 
-                TreeEntry::new(TreeEntryMode::Blob, name, sha)
-            };
+        let entries = index
+            .entries()
+            .iter()
+            .filter(|e| !e.intent_to_add && e.path.starts_with(&prefix))
+            .collect::<Vec<_>>();
 
-            tree_entries.push(tree_entry);
+        if !prefix.is_empty() && entries.is_empty() {
+            return Err(Error::Generic(format!(
+                "prefix {} not found",
+                prefix.trim_end_matches('/')
+            )));
         }
 
-        let mut tree = Tree::new(tree_entries);
-        tree.sort_entries();
+        if !missing_ok {
+            // Submodule commits live in another repository.
+            let missing = entries
+                .iter()
+                .find(|e| e.mode != MODE_GITLINK && !odb.contains(&e.id));
+
+            if let Some(entry) = missing {
+                return Err(Error::Generic(format!(
+                    "invalid object {:o} {} for '{}'",
+                    entry.mode, entry.id, entry.path
+                )));
+            }
+        }
 
-        let object = Object::from_tree(tree);
+        write_index_tree(odb, &entries, prefix.len())
+    }
 
-        store::write(&object)
+    /// Hashes every file below `root` and writes the trees for them, skipping only `.git`.
+    /// Executable files and symlinks keep their modes; symlinks are stored as their target.
+    /// Nested repositories become gitlinks. Like git, directories without files are left out.
+    pub fn from_work_tree(odb: &dyn ObjectDatabase, root: impl AsRef<Path>) -> Result<ObjectId> {
+        match write_work_tree_dir(odb, root.as_ref())? {
+            Some(sha) => Ok(sha),
+            None => odb.write(&Object::from_tree(Tree::new(Vec::new()))),
+        }
     }
 
-    pub fn exec(self) -> Result<()> {
-        let sha = WriteTree::inner(".")?;
+    pub fn exec(self, repo: &Repository) -> Result<()> {
+        let sha = match self.from_work_tree {
+            true => WriteTree::from_work_tree(repo.odb(), repo.require_work_tree()?)?,
+            false => WriteTree::from_index(
+                repo.odb(),
+                &repo.index()?,
+                self.prefix.as_deref(),
+                self.missing_ok,
+            )?,
+        };
 
         println!("{}", sha);
 
         Ok(())
     }
 }
+
+/// Writes the tree for `entries`, which are sorted and share the first `base` bytes of their
+/// paths, after writing a subtree for each directory among them.
+fn write_index_tree(
+    odb: &dyn ObjectDatabase,
+    entries: &[&IndexEntry],
+    base: usize,
+) -> Result<ObjectId> {
+    let mut tree_entries = Vec::new();
+    let mut rest = entries;
+
+    while let Some(entry) = rest.first() {
+        let name = &entry.path[base..];
+
+        match name.split_once('/') {
+            Some((dir, _)) => {
+                // Sorting keeps the entries of a directory next to each other.
+                let dir_prefix = &entry.path[..base + dir.len() + 1];
+                let len = rest
+                    .iter()
+                    .take_while(|e| e.path.starts_with(dir_prefix))
+                    .count();
+
+                let sha = write_index_tree(odb, &rest[..len], dir_prefix.len())?;
+                tree_entries.push(TreeEntry::new(TreeEntryMode::Tree, dir, sha));
+                rest = &rest[len..];
+            }
+            None => {
+                tree_entries.push(TreeEntry::new(entry.mode.try_into()?, name, entry.id));
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    let mut tree = Tree::new(tree_entries);
+    tree.sort_entries();
+
+    odb.write(&Object::from_tree(tree))
+}
+
+/// Writes the tree for the directory at `path`, or nothing if no files are below it.
+fn write_work_tree_dir(odb: &dyn ObjectDatabase, path: &Path) -> Result<Option<ObjectId>> {
+    let mut tree_entries = Vec::<TreeEntry>::new();
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name
+            .to_str()
+            .ok_or_else(|| Error::OsString(entry.file_name()))?;
+
+        if name == ".git" {
+            continue;
+        }
+
+        let metadata = fs::symlink_metadata(&path)?;
+
+        // Nested repositories are recorded as submodules at the commit checked out in them,
+        // and left out when none is.
+        let nested = metadata.is_dir().then(|| nested_git_dir(&path)).flatten();
+
+        let tree_entry = if let Some(git_dir) = nested {
+            match resolve_ref(&git_dir, "HEAD")?.1 {
+                Some(sha) => TreeEntry::new(TreeEntryMode::Gitlink, name, sha),
+                None => continue,
+            }
+        } else if metadata.is_dir() {
+            match write_work_tree_dir(odb, &path)? {
+                Some(sha) => TreeEntry::new(TreeEntryMode::Tree, name, sha),
+                None => continue,
+            }
+        } else {
+            let sha = hash_file(odb, &path, &metadata)?;
+
+            TreeEntry::new(mode_from_metadata(&metadata).try_into()?, name, sha)
+        };
+
+        tree_entries.push(tree_entry);
+    }
+
+    if tree_entries.is_empty() {
+        return Ok(None);
+    }
+
+    let mut tree = Tree::new(tree_entries);
+    tree.sort_entries();
+
+    odb.write(&Object::from_tree(tree)).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::{
+        index::entry::{MODE_EXECUTABLE, MODE_FILE, MODE_SYMLINK},
+        obj::{blob::Blob, store::MemoryObjectDatabase},
+        sha::ObjectFormat,
+    };
+
+    #[test]
+    fn test_from_index() {
+        let odb = MemoryObjectDatabase::new(ObjectFormat::Sha1);
+        let blob = odb
+            .write(&Object::from_blob(Blob::new(b"hello\n")))
+            .unwrap();
+
+        let mut index = Index::new(ObjectFormat::Sha1);
+        for path in ["a/b", "a/c/d", "e"] {
+            index.add(IndexEntry::new(path, blob, MODE_FILE));
+        }
+        index.add(IndexEntry::new("x", blob, MODE_EXECUTABLE));
+        index.add(IndexEntry::new("y", blob, MODE_SYMLINK));
+
+        // Matches `git write-tree` for the same index.
+        let id = WriteTree::from_index(&odb, &index, None, false).unwrap();
+        assert_eq!("86da8c964701f784431fb8d4365b13be3200c156", id.to_hex());
+
+        let tree = odb.read(&id).unwrap();
+        let names = tree.as_tree().unwrap().entry_names();
+        assert_eq!(vec!["a", "e", "x", "y"], names);
+
+        let sub = WriteTree::from_index(&odb, &index, Some("a/"), false).unwrap();
+        assert_eq!(sub, *tree.as_tree().unwrap().entry("a").unwrap().sha());
+        assert!(WriteTree::from_index(&odb, &index, Some("z"), false).is_err());
+
+        let mut conflicted = IndexEntry::new("e", blob, MODE_FILE);
+        conflicted.stage = 2;
+        index.add(conflicted);
+        assert!(WriteTree::from_index(&odb, &index, None, false).is_err());
+
+        index.remove("e");
+        let empty_odb = MemoryObjectDatabase::new(ObjectFormat::Sha1);
+        assert!(WriteTree::from_index(&empty_odb, &index, None, false).is_err());
+        assert!(WriteTree::from_index(&empty_odb, &index, None, true).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_from_work_tree() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let root = env::temp_dir().join(format!("write-tree-test-{}", process::id()));
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("file"), "hello\n").unwrap();
+        fs::write(root.join("sub/file"), "hello\n").unwrap();
+        fs::write(root.join("run"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(root.join("run"), fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("file", root.join("link")).unwrap();
+
+        let commit = "f0e388b3793b68987a5c626e65a3590244df9167";
+        let nested = Repository::init(&root.join("nested"), ObjectFormat::Sha1).unwrap();
+        fs::write(nested.git_dir().join("HEAD"), format!("{}\n", commit)).unwrap();
+        Repository::init(&root.join("uninitialized"), ObjectFormat::Sha1).unwrap();
+
+        // Matches `git write-tree` after adding the same files, with `nested` staged at
+        // `commit`. The repository without a commit is left out.
+        let odb = MemoryObjectDatabase::new(ObjectFormat::Sha1);
+        let id = WriteTree::from_work_tree(&odb, &root).unwrap();
+        assert_eq!("53b00be9446247edba147693154198786f94a71e", id.to_hex());
+
+        let tree = odb.read(&id).unwrap();
+        let tree = tree.as_tree().unwrap();
+        let modes = tree
+            .entries()
+            .iter()
+            .map(|e| (e.name(), e.mode()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("file", TreeEntryMode::Blob),
+                ("link", TreeEntryMode::Symlink),
+                ("nested", TreeEntryMode::Gitlink),
+                ("run", TreeEntryMode::BlobExecutable),
+                ("sub", TreeEntryMode::Tree),
+            ],
+            modes
+        );
+        assert_eq!(commit, tree.entry("nested").unwrap().sha().to_hex());
+
+        let link = odb.read(tree.entry("link").unwrap().sha()).unwrap();
+        assert_eq!(b"file", link.as_blob().unwrap().as_bytes());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
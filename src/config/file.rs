@@ -0,0 +1,545 @@
+use std::{fs, ops::Range, path::Path};
+
+use super::Key;
+use crate::{refs::lock::LockFile, Error, Result};
+
+/// A single `name = value` line in a config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub section: String,
+    pub subsection: Option<String>,
+    pub name: String,
+    /// `None` for a bare `name` line, which is an implicit boolean `true`.
+    pub value: Option<String>,
+    span: Range<usize>,
+}
+
+impl FileEntry {
+    pub fn matches(&self, key: &Key) -> bool {
+        self.section == key.section
+            && self.subsection == key.subsection
+            && self.name.eq_ignore_ascii_case(&key.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SectionHeader {
+    section: String,
+    subsection: Option<String>,
+    span: Range<usize>,
+}
+
+/// A parsed config file that keeps its original text, so that edits only touch the
+/// lines they change and comments and formatting survive a rewrite.
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    text: String,
+    sections: Vec<SectionHeader>,
+    entries: Vec<FileEntry>,
+}
+
+impl ConfigFile {
+    pub fn parse(text: impl ToString) -> Result<Self> {
+        let text = text.to_string();
+        let (sections, entries) = Parser::new(&text).parse()?;
+
+        Ok(Self {
+            text,
+            sections,
+            entries,
+        })
+    }
+
+    /// Reads the file at `path`, treating a missing file as empty.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        Self::parse(fs::read_to_string(path)?)
+            .map_err(|e| Error::Config(format!("{} in {}", e, path.display())))
+    }
+
+    /// Writes the file through `<path>.lock`, so that a crash never leaves it half written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut lock = LockFile::acquire(path.as_ref())?;
+        lock.write_all(self.text.as_bytes())?;
+
+        lock.commit()
+    }
+
+    pub fn entries(&self) -> &[FileEntry] {
+        &self.entries
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the value of `key`, or adds it to the end of its section, creating the
+    /// section if needed. Like `unset`, refuses a key with several values.
+    pub fn set(&mut self, key: &Key, value: &str) -> Result<()> {
+        let line = format!("\t{} = {}\n", key.name, quote(value));
+        let mut matches = self.entries.iter().filter(|e| e.matches(key));
+
+        if let Some(entry) = matches.next() {
+            if matches.next().is_some() {
+                return Err(Error::Config(format!("{} has multiple values", key)));
+            }
+
+            let span = entry.span.clone();
+            let indent = &self.text[span.start..]
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect::<String>();
+            let line = format!("{}{} = {}\n", indent, key.name, quote(value));
+
+            return self.splice(span, &line);
+        }
+
+        let section_end = self
+            .sections
+            .iter()
+            .filter(|s| s.section == key.section && s.subsection == key.subsection)
+            .map(|s| {
+                self.entries
+                    .iter()
+                    .filter(|e| e.span.start >= s.span.end && self.section_at(e) == Some(s))
+                    .map(|e| e.span.end)
+                    .max()
+                    .unwrap_or_else(|| self.line_end(s.span.end))
+            })
+            .next_back();
+
+        match section_end {
+            Some(position) => self.splice(position..position, &line),
+            None => {
+                let mut text = String::new();
+
+                if !self.text.is_empty() && !self.text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&key.section_header());
+                text.push('\n');
+                text.push_str(&line);
+
+                let end = self.text.len();
+                self.splice(end..end, &text)
+            }
+        }
+    }
+
+    /// Removes values of `key` and returns how many were removed. Unless `all` is set,
+    /// a key with several values is left untouched and reported as an error.
+    pub fn unset(&mut self, key: &Key, all: bool) -> Result<usize> {
+        let spans = self
+            .entries
+            .iter()
+            .filter(|e| e.matches(key))
+            .map(|e| e.span.clone())
+            .collect::<Vec<_>>();
+
+        if spans.len() > 1 && !all {
+            return Err(Error::Config(format!("{} has multiple values", key)));
+        }
+
+        for span in spans.iter().rev() {
+            self.text.replace_range(span.clone(), "");
+        }
+        self.reparse()?;
+
+        Ok(spans.len())
+    }
+
+    fn section_at(&self, entry: &FileEntry) -> Option<&SectionHeader> {
+        self.sections
+            .iter()
+            .take_while(|s| s.span.start < entry.span.start)
+            .last()
+    }
+
+    fn line_end(&self, position: usize) -> usize {
+        self.text[position..]
+            .find('\n')
+            .map_or(self.text.len(), |offset| position + offset + 1)
+    }
+
+    fn splice(&mut self, range: Range<usize>, replacement: &str) -> Result<()> {
+        self.text.replace_range(range, replacement);
+        self.reparse()
+    }
+
+    fn reparse(&mut self) -> Result<()> {
+        let (sections, entries) = Parser::new(&self.text).parse()?;
+        self.sections = sections;
+        self.entries = entries;
+
+        Ok(())
+    }
+}
+
+/// Quotes and escapes `value` so that it reads back unchanged.
+fn quote(value: &str) -> String {
+    let needs_quotes = value.starts_with([' ', '\t'])
+        || value.ends_with([' ', '\t'])
+        || value.contains(['#', ';']);
+
+    let mut result = String::with_capacity(value.len() + 2);
+
+    if needs_quotes {
+        result.push('"');
+    }
+
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\u{8}' => result.push_str("\\b"),
+            c => result.push(c),
+        }
+    }
+
+    if needs_quotes {
+        result.push('"');
+    }
+
+    result
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.as_bytes(),
+            position: 0,
+            line: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.position += 1;
+
+        if c == b'\n' {
+            self.line += 1;
+        }
+
+        Some(c)
+    }
+
+    fn error(&self) -> Error {
+        Error::Config(format!("bad config line {}", self.line))
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(b' ' | b'\t' | b'\r') = self.peek() {
+            self.next();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.next() {
+            if c == b'\n' {
+                break;
+            }
+        }
+    }
+
+    fn text(&self, range: Range<usize>) -> Result<String> {
+        String::from_utf8(self.input[range].to_vec()).map_err(|_| self.error())
+    }
+
+    fn parse(mut self) -> Result<(Vec<SectionHeader>, Vec<FileEntry>)> {
+        let mut sections = Vec::<SectionHeader>::new();
+        let mut entries = Vec::new();
+
+        loop {
+            let line_start = self.position;
+            self.skip_blanks();
+
+            match self.peek() {
+                None => break,
+                Some(b'\n') => {
+                    self.next();
+                }
+                Some(b'#' | b';') => self.skip_line(),
+                Some(b'[') => {
+                    let header = self.parse_section_header(line_start)?;
+                    sections.push(header);
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let header = sections.last().ok_or_else(|| self.error())?;
+                    let entry = self.parse_entry(line_start, header)?;
+                    entries.push(entry);
+                }
+                Some(_) => return Err(self.error()),
+            }
+        }
+
+        Ok((sections, entries))
+    }
+
+    fn parse_section_header(&mut self, start: usize) -> Result<SectionHeader> {
+        self.next();
+
+        let name_start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == b'-' || c == b'.' {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        let name = self.text(name_start..self.position)?;
+
+        if name.is_empty() {
+            return Err(self.error());
+        }
+
+        let (section, subsection) = match self.peek() {
+            Some(b']') => match name.split_once('.') {
+                // deprecated `[section.subsection]` syntax, subsection is case-insensitive
+                Some((section, subsection)) => {
+                    (section.to_owned(), Some(subsection.to_lowercase()))
+                }
+                None => (name, None),
+            },
+            Some(b' ' | b'\t') if !name.contains('.') => {
+                self.skip_blanks();
+
+                if self.next() != Some(b'"') {
+                    return Err(self.error());
+                }
+
+                let mut subsection = Vec::new();
+                loop {
+                    match self.next() {
+                        Some(b'"') => break,
+                        Some(b'\\') => subsection.push(self.next().ok_or_else(|| self.error())?),
+                        Some(b'\n') | None => return Err(self.error()),
+                        Some(c) => subsection.push(c),
+                    }
+                }
+
+                let subsection = String::from_utf8(subsection).map_err(|_| self.error())?;
+                (name, Some(subsection))
+            }
+            _ => return Err(self.error()),
+        };
+
+        if self.next() != Some(b']') {
+            return Err(self.error());
+        }
+
+        Ok(SectionHeader {
+            section: section.to_lowercase(),
+            subsection,
+            span: start..self.position,
+        })
+    }
+
+    fn parse_entry(&mut self, start: usize, header: &SectionHeader) -> Result<FileEntry> {
+        let name_start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == b'-' {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        let name = self.text(name_start..self.position)?;
+
+        self.skip_blanks();
+
+        let value = match self.peek() {
+            Some(b'=') => {
+                self.next();
+                Some(self.parse_value()?)
+            }
+            Some(b'\n') => {
+                self.next();
+                None
+            }
+            Some(b'#' | b';') => {
+                self.skip_line();
+                None
+            }
+            None => None,
+            Some(_) => return Err(self.error()),
+        };
+
+        Ok(FileEntry {
+            section: header.section.clone(),
+            subsection: header.subsection.clone(),
+            name: name.to_lowercase(),
+            value,
+            span: start..self.position,
+        })
+    }
+
+    /// Parses a value up to and including the end of its (last) line, handling quotes,
+    /// escapes, inline comments and backslash line continuations.
+    fn parse_value(&mut self) -> Result<String> {
+        let mut value = Vec::new();
+        let mut pending_spaces = 0;
+        let mut quoted = false;
+
+        loop {
+            let c = match self.next() {
+                None if quoted => return Err(self.error()),
+                None => break,
+                Some(c) => c,
+            };
+
+            match c {
+                b'\n' if quoted => return Err(self.error()),
+                b'\n' => break,
+                b'#' | b';' if !quoted => {
+                    self.skip_line();
+                    break;
+                }
+                b' ' | b'\t' | b'\r' if !quoted => {
+                    if !value.is_empty() {
+                        pending_spaces += 1;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            value.extend(std::iter::repeat_n(b' ', pending_spaces));
+            pending_spaces = 0;
+
+            match c {
+                b'\\' => match self.next() {
+                    Some(b'\n') => {}
+                    Some(b't') => value.push(b'\t'),
+                    Some(b'n') => value.push(b'\n'),
+                    Some(b'b') => value.push(0x08),
+                    Some(c @ (b'\\' | b'"')) => value.push(c),
+                    _ => return Err(self.error()),
+                },
+                b'"' => quoted = !quoted,
+                c => value.push(c),
+            }
+        }
+
+        String::from_utf8(value).map_err(|_| self.error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(file: &ConfigFile) -> Vec<(String, Option<String>, String, Option<String>)> {
+        file.entries()
+            .iter()
+            .map(|e| {
+                (
+                    e.section.clone(),
+                    e.subsection.clone(),
+                    e.name.clone(),
+                    e.value.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let file = ConfigFile::parse(
+            "# comment\n\
+             [Core]\n\
+             \tBare = false ; trailing comment\n\
+             \tbool\n\
+             [remote \"Origin \\\"x\\\"\"]\n\
+             \turl = \" spaced # value \"  \n\
+             \tescaped = a\\tb\\\\c\\\n  continued\n\
+             [branch.Main] merge = refs/heads/main\n",
+        )
+        .unwrap();
+
+        let entry = |section: &str, subsection: Option<&str>, name: &str, value: Option<&str>| {
+            (
+                section.to_owned(),
+                subsection.map(String::from),
+                name.to_owned(),
+                value.map(String::from),
+            )
+        };
+
+        assert_eq!(
+            vec![
+                entry("core", None, "bare", Some("false")),
+                entry("core", None, "bool", None),
+                entry(
+                    "remote",
+                    Some("Origin \"x\""),
+                    "url",
+                    Some(" spaced # value ")
+                ),
+                entry(
+                    "remote",
+                    Some("Origin \"x\""),
+                    "escaped",
+                    Some("a\tb\\c  continued")
+                ),
+                entry("branch", Some("main"), "merge", Some("refs/heads/main")),
+            ],
+            values(&file)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(ConfigFile::parse("key = value\n").is_err());
+        assert!(ConfigFile::parse("[core\n").is_err());
+        assert!(ConfigFile::parse("[core]\nkey = \"open\n").is_err());
+        assert!(ConfigFile::parse("[core]\nkey = bad\\escape\n").is_err());
+    }
+
+    #[test]
+    fn test_set_and_unset_preserve_comments() {
+        let mut file =
+            ConfigFile::parse("# top\n[core]\n\tbare = false # keep\n\n[user]\n\tname = A\n")
+                .unwrap();
+
+        file.set(&"core.bare".parse().unwrap(), "true").unwrap();
+        file.set(&"core.filemode".parse().unwrap(), "true").unwrap();
+        file.set(&"remote.origin.url".parse().unwrap(), " x;y ")
+            .unwrap();
+        file.unset(&"user.name".parse().unwrap(), false).unwrap();
+
+        assert_eq!(
+            "# top\n[core]\n\tbare = true\n\tfilemode = true\n\n[user]\n\
+             [remote \"origin\"]\n\turl = \" x;y \"\n",
+            file.as_str()
+        );
+        assert_eq!(
+            Some(String::from(" x;y ")),
+            file.entries().last().unwrap().value
+        );
+
+        let mut file =
+            ConfigFile::parse("[remote \"origin\"]\n\tfetch = a\n\tfetch = b\n").unwrap();
+        let before = file.as_str().to_owned();
+        assert!(file
+            .set(&"remote.origin.fetch".parse().unwrap(), "c")
+            .is_err());
+        assert_eq!(before, file.as_str());
+    }
+}
@@ -0,0 +1,554 @@
+pub mod file;
+
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use self::file::ConfigFile;
+use crate::{Error, Result};
+
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Where a config value came from, in increasing order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    System,
+    Global,
+    Local,
+    Worktree,
+    Command,
+}
+
+impl Scope {
+    /// Returns the files backing this scope, lowest precedence first.
+    pub fn paths(&self, git_dir: Option<&Path>) -> Vec<PathBuf> {
+        match self {
+            Scope::System => {
+                if env::var_os("GIT_CONFIG_NOSYSTEM").is_some() {
+                    return Vec::new();
+                }
+
+                vec![env::var_os("GIT_CONFIG_SYSTEM")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from("/etc/gitconfig"))]
+            }
+            Scope::Global => {
+                if let Some(path) = env::var_os("GIT_CONFIG_GLOBAL") {
+                    return vec![PathBuf::from(path)];
+                }
+
+                let home = match env::var_os("HOME") {
+                    Some(home) => PathBuf::from(home),
+                    None => return Vec::new(),
+                };
+                let xdg_config_home = env::var_os("XDG_CONFIG_HOME")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| home.join(".config"));
+
+                vec![xdg_config_home.join("git/config"), home.join(".gitconfig")]
+            }
+            Scope::Local => git_dir.map(|dir| dir.join("config")).into_iter().collect(),
+            Scope::Worktree => git_dir
+                .map(|dir| dir.join("config.worktree"))
+                .into_iter()
+                .collect(),
+            Scope::Command => Vec::new(),
+        }
+    }
+}
+
+/// A config key in `section[.subsection].name` form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    pub section: String,
+    pub subsection: Option<String>,
+    pub name: String,
+}
+
+impl Key {
+    pub fn section_header(&self) -> String {
+        match &self.subsection {
+            Some(subsection) => format!(
+                "[{} \"{}\"]",
+                self.section,
+                subsection.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+            None => format!("[{}]", self.section),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::Config(format!("invalid key: {}", s));
+
+        let (section, rest) = s.split_once('.').ok_or_else(invalid)?;
+        let (subsection, name) = match rest.rsplit_once('.') {
+            Some((subsection, name)) => (Some(subsection.to_owned()), name),
+            None => (None, rest),
+        };
+
+        let valid_section = !section.is_empty()
+            && section
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || c == b'-');
+        let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-');
+
+        if !valid_section || !valid_name || subsection.as_deref().is_some_and(|s| s.contains('\n'))
+        {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            section: section.to_lowercase(),
+            subsection,
+            name: name.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.subsection {
+            Some(subsection) => write!(f, "{}.{}.{}", self.section, subsection, self.name),
+            None => write!(f, "{}.{}", self.section, self.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub key: Key,
+    /// `None` for a bare `name` line, which is an implicit boolean `true`.
+    pub value: Option<String>,
+    pub scope: Scope,
+    pub origin: Option<PathBuf>,
+}
+
+/// Configuration values merged from all scopes, in increasing order of precedence.
+#[derive(Debug, Default)]
+pub struct Config {
+    entries: Vec<Entry>,
+}
+
+impl Config {
+    /// Loads the system, global, local and worktree config files for the repository at
+    /// `git_dir`, followed by values passed through `GIT_CONFIG_COUNT`. Outside of a
+    /// repository only the system and global files are read.
+    pub fn load(git_dir: Option<&Path>) -> Result<Self> {
+        let mut config = Self::default();
+
+        for scope in [Scope::System, Scope::Global, Scope::Local] {
+            for path in scope.paths(git_dir) {
+                config.read_file(&path, scope, git_dir, 0)?;
+            }
+        }
+
+        if config.get_bool("extensions.worktreeConfig")? == Some(true) {
+            for path in Scope::Worktree.paths(git_dir) {
+                config.read_file(&path, Scope::Worktree, git_dir, 0)?;
+            }
+        }
+
+        config.read_env()?;
+
+        Ok(config)
+    }
+
+    /// Loads a single file, as with `git config --file`.
+    pub fn load_file(path: &Path, scope: Scope, git_dir: Option<&Path>) -> Result<Self> {
+        let mut config = Self::default();
+        config.read_file(path, scope, git_dir, 0)?;
+
+        Ok(config)
+    }
+
+    fn read_file(
+        &mut self,
+        path: &Path,
+        scope: Scope,
+        git_dir: Option<&Path>,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(Error::Config(format!(
+                "exceeded maximum include depth while including {}",
+                path.display()
+            )));
+        }
+
+        if !path.is_file() {
+            return Ok(());
+        }
+
+        let file = ConfigFile::open(path)?;
+
+        for entry in file.entries() {
+            let key = Key {
+                section: entry.section.clone(),
+                subsection: entry.subsection.clone(),
+                name: entry.name.clone(),
+            };
+
+            let include = match (&entry.value, key.section.as_str(), &key.subsection) {
+                (Some(include), "include", None) if key.name == "path" => Some(include),
+                (Some(include), "includeif", Some(condition)) if key.name == "path" => {
+                    Some(include).filter(|_| include_condition(condition, path, git_dir))
+                }
+                _ => None,
+            };
+
+            self.entries.push(Entry {
+                key,
+                value: entry.value.clone(),
+                scope,
+                origin: Some(path.to_owned()),
+            });
+
+            if let Some(include) = include {
+                let include = expand_path(include)?;
+                let include = match path.parent() {
+                    Some(parent) if include.is_relative() => parent.join(include),
+                    _ => include,
+                };
+
+                self.read_file(&include, scope, git_dir, depth + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_env(&mut self) -> Result<()> {
+        let count = match env::var("GIT_CONFIG_COUNT") {
+            Ok(count) => count
+                .parse::<usize>()
+                .map_err(|_| Error::Config(format!("bogus GIT_CONFIG_COUNT: {}", count)))?,
+            Err(_) => return Ok(()),
+        };
+
+        for i in 0..count {
+            let key = env::var(format!("GIT_CONFIG_KEY_{}", i))
+                .map_err(|_| Error::Config(format!("missing config key GIT_CONFIG_KEY_{}", i)))?;
+            let value = env::var(format!("GIT_CONFIG_VALUE_{}", i)).map_err(|_| {
+                Error::Config(format!("missing config value GIT_CONFIG_VALUE_{}", i))
+            })?;
+
+            self.entries.push(Entry {
+                key: key.parse()?,
+                value: Some(value),
+                scope: Scope::Command,
+                origin: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn extend(&mut self, other: Config) {
+        self.entries.extend(other.entries);
+    }
+
+    /// Returns all entries for `key`, lowest precedence first.
+    pub fn values(&self, key: &str) -> Result<impl DoubleEndedIterator<Item = &Entry>> {
+        let key = key.parse::<Key>()?;
+
+        Ok(self.entries.iter().filter(move |e| {
+            e.key.section == key.section
+                && e.key.subsection == key.subsection
+                && e.key.name.eq_ignore_ascii_case(&key.name)
+        }))
+    }
+
+    /// Returns the last value set for `key`; a bare `name` line reads as an empty string.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values(key)
+            .ok()?
+            .last()
+            .map(|e| e.value.as_deref().unwrap_or_default())
+    }
+
+    /// Returns all values set for a multi-valued `key`, in order.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.values(key)
+            .map(|values| {
+                values
+                    .map(|e| e.value.as_deref().unwrap_or_default())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        match self.values(key)?.last() {
+            Some(entry) => match &entry.value {
+                None => Ok(Some(true)),
+                Some(value) => parse_bool(value).map(Some).ok_or_else(|| {
+                    Error::Config(format!(
+                        "bad boolean config value '{}' for '{}'",
+                        value, key
+                    ))
+                }),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_int(&self, key: &str) -> Result<Option<i64>> {
+        match self.get(key) {
+            Some(value) => parse_int(value).map(Some).ok_or_else(|| {
+                Error::Config(format!(
+                    "bad numeric config value '{}' for '{}'",
+                    value, key
+                ))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_path(&self, key: &str) -> Result<Option<PathBuf>> {
+        self.get(key).map(expand_path).transpose()
+    }
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" | "" => Some(false),
+        value => value.parse::<i64>().ok().map(|n| n != 0),
+    }
+}
+
+/// Parses an integer with an optional `k`, `m` or `g` suffix (powers of 1024).
+pub fn parse_int(value: &str) -> Option<i64> {
+    let value = value.trim();
+
+    let (digits, factor) = match value.chars().last()?.to_ascii_lowercase() {
+        'k' => (&value[..value.len() - 1], 1 << 10),
+        'm' => (&value[..value.len() - 1], 1 << 20),
+        'g' => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+
+    digits.parse::<i64>().ok()?.checked_mul(factor)
+}
+
+/// Expands a leading `~/` to the home directory.
+pub fn expand_path(value: &str) -> Result<PathBuf> {
+    match value.strip_prefix("~/") {
+        Some(rest) => env::var_os("HOME")
+            .map(|home| Path::new(&home).join(rest))
+            .ok_or_else(|| Error::Config(format!("cannot expand {}, HOME is not set", value))),
+        None => Ok(PathBuf::from(value)),
+    }
+}
+
+/// Evaluates an `includeIf` condition (`gitdir:`, `gitdir/i:` or `onbranch:`).
+fn include_condition(condition: &str, config_path: &Path, git_dir: Option<&Path>) -> bool {
+    let git_dir = match git_dir {
+        Some(git_dir) => git_dir,
+        None => return false,
+    };
+
+    if let Some(pattern) = condition.strip_prefix("onbranch:") {
+        let head = fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+
+        return match head.trim_end().strip_prefix("ref: refs/heads/") {
+            Some(branch) => wildmatch(&directory_pattern(pattern.to_owned()), branch, false),
+            None => false,
+        };
+    }
+
+    let (pattern, ignore_case) = match condition.strip_prefix("gitdir:") {
+        Some(pattern) => (pattern, false),
+        None => match condition.strip_prefix("gitdir/i:") {
+            Some(pattern) => (pattern, true),
+            None => return false,
+        },
+    };
+
+    let pattern = match pattern.strip_prefix("./") {
+        Some(rest) => match config_path.parent() {
+            Some(parent) => format!("{}/{}", parent.display(), rest),
+            None => return false,
+        },
+        None => match expand_path(pattern) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(_) => return false,
+        },
+    };
+    let pattern = if pattern.starts_with('/') {
+        pattern
+    } else {
+        format!("**/{}", pattern)
+    };
+
+    let git_dir = match git_dir.canonicalize() {
+        Ok(git_dir) => git_dir.to_string_lossy().into_owned(),
+        Err(_) => return false,
+    };
+
+    wildmatch(&directory_pattern(pattern), &git_dir, ignore_case)
+}
+
+/// A pattern ending with `/` matches everything inside that directory.
+fn directory_pattern(pattern: String) -> String {
+    if pattern.ends_with('/') {
+        pattern + "**"
+    } else {
+        pattern
+    }
+}
+
+/// Matches `text` against a glob where `*` and `?` do not cross `/` and `**` matches anything.
+pub fn wildmatch(pattern: &str, text: &str, ignore_case: bool) -> bool {
+    fn matches(pattern: &[u8], text: &[u8], ignore_case: bool) -> bool {
+        match pattern.first() {
+            None => text.is_empty(),
+            Some(b'*') if pattern.get(1) == Some(&b'*') => {
+                let rest = pattern[2..].strip_prefix(b"/").unwrap_or(&pattern[2..]);
+
+                (0..=text.len()).any(|i| {
+                    (i == 0 || text[i - 1] == b'/') && matches(rest, &text[i..], ignore_case)
+                }) || matches(&pattern[2..], &text[text.len()..], ignore_case)
+            }
+            Some(b'*') => (0..=text.len())
+                .take_while(|&i| i == 0 || text[i - 1] != b'/')
+                .any(|i| matches(&pattern[1..], &text[i..], ignore_case)),
+            Some(b'?') => match text.first() {
+                Some(c) if *c != b'/' => matches(&pattern[1..], &text[1..], ignore_case),
+                _ => false,
+            },
+            Some(p) => match text.first() {
+                Some(c) if p == c || (ignore_case && p.eq_ignore_ascii_case(c)) => {
+                    matches(&pattern[1..], &text[1..], ignore_case)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    matches(pattern.as_bytes(), text.as_bytes(), ignore_case)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        let key = "Remote.Origin.URL".parse::<Key>().unwrap();
+
+        assert_eq!("remote", key.section);
+        assert_eq!(Some(String::from("Origin")), key.subsection);
+        assert_eq!("URL", key.name);
+        assert!("core".parse::<Key>().is_err());
+        assert!("core.1bare".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn test_typed_values() {
+        assert_eq!(Some(true), parse_bool("Yes"));
+        assert_eq!(Some(false), parse_bool(""));
+        assert_eq!(None, parse_bool("maybe"));
+        assert_eq!(Some(2048), parse_int("2k"));
+        assert_eq!(Some(3 << 30), parse_int("3G"));
+        assert_eq!(None, parse_int("12x"));
+    }
+
+    #[test]
+    fn test_wildmatch() {
+        assert!(wildmatch("**/work/**", "/home/u/work/repo/.git", false));
+        assert!(wildmatch("/home/*/.git", "/home/u/.git", false));
+        assert!(!wildmatch("/home/*/.git", "/home/u/x/.git", false));
+        assert!(wildmatch("feature/**", "feature/a/b", false));
+        assert!(wildmatch("/HOME/**", "/home/u", true));
+    }
+
+    #[test]
+    fn test_includes_and_scopes() {
+        let dir = env::temp_dir().join(format!("config-test-{}", process::id()));
+        let git_dir = dir.join("repo/.git");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let git_dir = git_dir.canonicalize().unwrap();
+
+        let write = |name: &str, text: String| {
+            let path = dir.join(name);
+            fs::write(&path, text).unwrap();
+            path
+        };
+        let system = write(
+            "system",
+            String::from("[user]\n\tname = System\n[core]\n\tabbrev = 4\n"),
+        );
+        let global = write(
+            "global",
+            String::from("[user]\n\tname = Global\n[include]\n\tpath = included\n"),
+        );
+        write(
+            "included",
+            String::from("[user]\n\temail = included@example.com\n"),
+        );
+        write("work", String::from("[user]\n\temail = work@example.com\n"));
+        write("main", String::from("[core]\n\tabbrev = 8\n"));
+        write("other", String::from("[core]\n\tabbrev = 12\n"));
+        let local = git_dir.join("config");
+        fs::write(
+            &local,
+            format!(
+                "[includeIf \"gitdir:{}/repo/\"]\n\tpath = {}\n\
+                 [includeIf \"gitdir:/elsewhere/\"]\n\tpath = {}\n\
+                 [includeIf \"onbranch:main\"]\n\tpath = {}\n\
+                 [includeIf \"onbranch:other\"]\n\tpath = {}\n",
+                dir.canonicalize().unwrap().display(),
+                dir.join("work").display(),
+                dir.join("other").display(),
+                dir.join("main").display(),
+                dir.join("other").display(),
+            ),
+        )
+        .unwrap();
+
+        let mut config = Config::load_file(&system, Scope::System, Some(&git_dir)).unwrap();
+        config.extend(Config::load_file(&global, Scope::Global, Some(&git_dir)).unwrap());
+        config.extend(Config::load_file(&local, Scope::Local, Some(&git_dir)).unwrap());
+
+        // Later scopes win, and included values take the scope of the including file.
+        assert_eq!(Some("Global"), config.get("user.name"));
+        assert_eq!(Some("work@example.com"), config.get("user.email"));
+        assert_eq!(Some(8), config.get_int("core.abbrev").unwrap());
+        assert_eq!(
+            vec![Scope::Global, Scope::Local],
+            config
+                .values("user.email")
+                .unwrap()
+                .map(|e| e.scope)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(dir.join("included")),
+            config.values("user.email").unwrap().next().unwrap().origin
+        );
+
+        // Outside of a repository, conditional includes never match.
+        let config = Config::load_file(&local, Scope::Local, None).unwrap();
+        assert_eq!(None, config.get("user.email"));
+
+        let looping = write("looping", String::from("[include]\n\tpath = looping\n"));
+        assert!(Config::load_file(&looping, Scope::Global, None).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,11 +1,13 @@
 use thiserror::Error;
 
+use crate::obj::id::ObjectId;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("first letter must be lowercase but was {0}")]
     WrongCase(String),
     #[error("IO error")]
-    IoError(#[from] std::io::Error),
+    IoError(#[source] std::io::Error),
     #[error("utf-8 error")]
     Utf8Error(#[from] std::str::Utf8Error),
     #[error("from utf-8 error")]
@@ -18,8 +20,65 @@ pub enum Error {
     ParseCommand(String),
     #[error("failed parse object - {0}")]
     ParseObject(String),
+    #[error("failed parse object - expected {expected} at byte {offset}")]
+    MalformedObject {
+        offset: usize,
+        expected: &'static str,
+    },
+    #[error("object {id} is corrupt: expected {expected}, found {actual}")]
+    CorruptObject {
+        id: ObjectId,
+        expected: String,
+        actual: String,
+    },
+    #[error(
+        "{0} identity unknown, set GIT_{upper}_NAME and GIT_{upper}_EMAIL or user.name and user.email in config",
+        upper = .0.to_uppercase()
+    )]
+    UnknownIdentity(&'static str),
+    #[error("invalid object id: {0}")]
+    InvalidObjectId(String),
+    #[error("not a valid object name {0}")]
+    ObjectNotFound(String),
+    #[error(
+        "short object id {prefix} is ambiguous, the candidates are:{}",
+        format_candidates(candidates)
+    )]
+    AmbiguousObjectId {
+        prefix: String,
+        /// Abbreviated ids of the matching objects and their types.
+        candidates: Vec<(String, String)>,
+    },
+    #[error("ambiguous argument '{0}': unknown revision or path not in the working tree")]
+    UnknownRevision(String),
+    #[error("'{0}' is not a valid ref name")]
+    InvalidRefName(String),
+    #[error("{0}")]
+    Config(String),
+    #[error("invalid date format: {0}")]
+    InvalidDate(String),
+    #[error("not a git repository (or any of the parent directories): {}", .0.display())]
+    NotARepository(std::path::PathBuf),
     #[error("{0}")]
     Generic(String),
 }
 
+impl From<std::io::Error> for Error {
+    /// Unwraps errors of this crate that had to travel through an [`std::io::Error`], such as
+    /// corrupt objects noticed while streaming their content through a reader.
+    fn from(e: std::io::Error) -> Self {
+        match e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            true => *e.into_inner().unwrap().downcast::<Error>().unwrap(),
+            false => Self::IoError(e),
+        }
+    }
+}
+
+fn format_candidates(candidates: &[(String, String)]) -> String {
+    candidates
+        .iter()
+        .map(|(id, kind)| format!("\n  {} {}", id, kind))
+        .collect()
+}
+
 pub type Result<T> = core::result::Result<T, Error>;
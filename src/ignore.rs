@@ -0,0 +1,225 @@
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{wildmatch, Config},
+    Result,
+};
+
+/// One line of an ignore file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IgnorePattern {
+    pattern: String,
+    /// `!pattern`: re-includes what an earlier pattern excluded.
+    negated: bool,
+    /// `pattern/`: only matches directories.
+    dir_only: bool,
+    /// The pattern has a `/` before its end, so it matches paths relative to the directory of
+    /// its file rather than any file name below it.
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = trim_trailing_spaces(line);
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let pattern = line.strip_prefix('/').unwrap_or(line);
+
+        (!pattern.is_empty()).then(|| Self {
+            pattern: pattern.replace("\\ ", " "),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// Matches `path`, relative to the directory of the pattern's file.
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        match self.anchored {
+            true => wildmatch(&self.pattern, path, false),
+            false => {
+                let name = path.rsplit_once('/').map_or(path, |(_, name)| name);
+                wildmatch(&self.pattern, name, false)
+            }
+        }
+    }
+}
+
+/// Trailing spaces are dropped unless escaped with a backslash.
+fn trim_trailing_spaces(line: &str) -> &str {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let trimmed = line.trim_end_matches(' ');
+
+    match trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        true => &line[..trimmed.len() + 1],
+        false => trimmed,
+    }
+}
+
+/// The ignore rules of a work tree: `.gitignore` files, `$GIT_DIR/info/exclude` and
+/// `core.excludesFile`, in decreasing order of precedence. `.gitignore` files are read as
+/// the directories holding them are first looked at.
+#[derive(Debug)]
+pub struct Ignore {
+    work_tree: PathBuf,
+    /// `.gitignore` patterns by directory, `""` for the top or `a/b/` below it.
+    dirs: HashMap<String, Vec<IgnorePattern>>,
+    /// `info/exclude` followed by `core.excludesFile`, which match relative to the top.
+    global: Vec<Vec<IgnorePattern>>,
+}
+
+impl Ignore {
+    pub fn new(work_tree: &Path, git_dir: &Path, config: &Config) -> Result<Self> {
+        let excludes_file = match config.get_path("core.excludesFile")? {
+            Some(path) => Some(path),
+            None => env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+                .map(|dir| dir.join("git/ignore")),
+        };
+
+        let mut global = vec![read_patterns(&git_dir.join("info/exclude"))?];
+        if let Some(path) = excludes_file {
+            global.push(read_patterns(&path)?);
+        }
+
+        Ok(Self {
+            work_tree: work_tree.to_owned(),
+            dirs: HashMap::new(),
+            global,
+        })
+    }
+
+    /// Whether the slash-separated `path` below the top of the work tree is ignored, either
+    /// itself or because a directory containing it is.
+    pub fn is_ignored(&mut self, path: &str, is_dir: bool) -> Result<bool> {
+        let mut end = 0;
+
+        while let Some(offset) = path[end..].find('/') {
+            end += offset;
+
+            if self.is_excluded(&path[..end], true)? {
+                return Ok(true);
+            }
+            end += 1;
+        }
+
+        self.is_excluded(path, is_dir)
+    }
+
+    /// Applies the patterns to `path` alone, assuming its parent directories are not ignored.
+    /// The deepest `.gitignore` with a matching pattern decides, and in each file the last
+    /// matching pattern.
+    pub fn is_excluded(&mut self, path: &str, is_dir: bool) -> Result<bool> {
+        let mut bases = vec![String::new()];
+        bases.extend(
+            path.match_indices('/')
+                .map(|(i, _)| path[..i + 1].to_owned()),
+        );
+
+        for base in &bases {
+            if !self.dirs.contains_key(base) {
+                let patterns = read_patterns(&self.work_tree.join(base).join(".gitignore"))?;
+                self.dirs.insert(base.clone(), patterns);
+            }
+        }
+
+        let found = bases
+            .iter()
+            .rev()
+            .map(|base| (&self.dirs[base], &path[base.len()..]))
+            .chain(self.global.iter().map(|patterns| (patterns, path)))
+            .find_map(|(patterns, path)| {
+                patterns
+                    .iter()
+                    .rev()
+                    .find(|pattern| pattern.matches(path, is_dir))
+            });
+
+        Ok(found.is_some_and(|pattern| !pattern.negated))
+    }
+}
+
+/// Reads the patterns of an ignore file, which may not exist.
+fn read_patterns(path: &Path) -> Result<Vec<IgnorePattern>> {
+    match fs::read(path) {
+        Ok(content) => Ok(String::from_utf8_lossy(&content)
+            .lines()
+            .filter_map(IgnorePattern::parse)
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound || path.is_dir() => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::config::Scope;
+
+    #[test]
+    fn test_ignore() {
+        let root = env::temp_dir().join(format!("ignore-test-{}", process::id()));
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "*.local\n").unwrap();
+        fs::write(
+            root.join(".gitignore"),
+            "# build output\n*.o\n!keep.o\n/target\nbuild/\ndoc/*.html\n\\#hash \n",
+        )
+        .unwrap();
+        fs::write(root.join("src/.gitignore"), "!*.o\ngen/\n").unwrap();
+        fs::write(root.join("global"), "*.swp\n").unwrap();
+        fs::write(
+            root.join(".git/config"),
+            format!(
+                "[core]\n\texcludesFile = {}\n",
+                root.join("global").display()
+            ),
+        )
+        .unwrap();
+
+        let config = Config::load_file(&root.join(".git/config"), Scope::Local, None).unwrap();
+        let mut ignore = Ignore::new(&root, &root.join(".git"), &config).unwrap();
+
+        assert!(ignore.is_ignored("a.o", false).unwrap());
+        assert!(ignore.is_ignored("lib/a.o", false).unwrap());
+        assert!(!ignore.is_ignored("keep.o", false).unwrap());
+        assert!(!ignore.is_ignored("src/a.o", false).unwrap());
+        assert!(ignore.is_ignored("target", true).unwrap());
+        assert!(ignore.is_ignored("target/debug/x", false).unwrap());
+        assert!(!ignore.is_ignored("src/target", true).unwrap());
+        assert!(ignore.is_ignored("a/build/x", false).unwrap());
+        assert!(!ignore.is_ignored("build", false).unwrap());
+        assert!(ignore.is_ignored("doc/index.html", false).unwrap());
+        assert!(!ignore.is_ignored("doc/api/index.html", false).unwrap());
+        assert!(ignore.is_ignored("src/gen/x.rs", false).unwrap());
+        assert!(ignore.is_ignored("#hash", false).unwrap());
+        assert!(ignore.is_ignored("settings.local", false).unwrap());
+        assert!(ignore.is_ignored("src/.main.rs.swp", false).unwrap());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
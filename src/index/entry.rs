@@ -0,0 +1,122 @@
+use std::fs::Metadata;
+
+use crate::obj::id::ObjectId;
+
+/// Mode of a regular file in the index.
+pub const MODE_FILE: u32 = 0o100644;
+/// Mode of an executable file in the index.
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+/// Mode of a symbolic link, whose blob holds the link target.
+pub const MODE_SYMLINK: u32 = 0o120000;
+/// Mode of a submodule, whose id is a commit in another repository.
+pub const MODE_GITLINK: u32 = 0o160000;
+
+/// A file timestamp as stored in the index, truncated to 32-bit seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct IndexTime {
+    pub secs: u32,
+    pub nanos: u32,
+}
+
+/// The `stat(2)` fields git caches for each entry to notice changes without rehashing files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatData {
+    pub ctime: IndexTime,
+    pub mtime: IndexTime,
+    pub dev: u32,
+    pub ino: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// The file size, truncated to 32 bits.
+    pub size: u32,
+}
+
+impl StatData {
+    #[cfg(unix)]
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        let time = |secs: i64, nanos: i64| IndexTime {
+            secs: secs as u32,
+            nanos: nanos as u32,
+        };
+
+        Self {
+            ctime: time(metadata.ctime(), metadata.ctime_nsec()),
+            mtime: time(metadata.mtime(), metadata.mtime_nsec()),
+            dev: metadata.dev() as u32,
+            ino: metadata.ino() as u32,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.len() as u32,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            size: metadata.len() as u32,
+            ..Self::default()
+        }
+    }
+}
+
+/// Returns the index mode for a file in the work tree: a symlink, or a regular file that is
+/// executable when any execute bit is set.
+pub fn mode_from_metadata(metadata: &Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if metadata.file_type().is_symlink() {
+            return MODE_SYMLINK;
+        }
+
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return MODE_EXECUTABLE;
+        }
+    }
+
+    #[cfg(not(unix))]
+    if metadata.file_type().is_symlink() {
+        return MODE_SYMLINK;
+    }
+
+    MODE_FILE
+}
+
+/// One path in the index, at one merge stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Slash-separated path relative to the top of the work tree.
+    pub path: String,
+    pub id: ObjectId,
+    pub mode: u32,
+    pub stat: StatData,
+    /// 0 for a merged entry, 1 to 3 for the base, ours and theirs sides of a conflict.
+    pub stage: u8,
+    pub assume_valid: bool,
+    pub skip_worktree: bool,
+    /// Added with `git add -N`: the path is tracked but its content is not staged yet.
+    pub intent_to_add: bool,
+}
+
+impl IndexEntry {
+    pub fn new(path: impl ToString, id: ObjectId, mode: u32) -> Self {
+        Self {
+            path: path.to_string(),
+            id,
+            mode,
+            stat: StatData::default(),
+            stage: 0,
+            assume_valid: false,
+            skip_worktree: false,
+            intent_to_add: false,
+        }
+    }
+
+    /// Whether the entry needs the extended flags only index version 3 and later can store.
+    pub fn has_extended_flags(&self) -> bool {
+        self.skip_worktree || self.intent_to_add
+    }
+}
@@ -0,0 +1,594 @@
+pub mod entry;
+
+use std::{
+    fs::{self, Metadata},
+    io,
+    path::Path,
+};
+
+use self::entry::{mode_from_metadata, IndexEntry, IndexTime, StatData};
+use crate::{
+    obj::id::ObjectId,
+    refs::lock::LockFile,
+    sha::{get_sha, ObjectFormat},
+    Error, Result,
+};
+
+const SIGNATURE: &[u8; 4] = b"DIRC";
+
+const FLAG_ASSUME_VALID: u16 = 0x8000;
+const FLAG_EXTENDED: u16 = 0x4000;
+const FLAG_STAGE_MASK: u16 = 0x3000;
+const FLAG_NAME_MASK: u16 = 0x0fff;
+
+const EXTENDED_SKIP_WORKTREE: u16 = 0x4000;
+const EXTENDED_INTENT_TO_ADD: u16 = 0x2000;
+
+/// Caches that describe the entries and go stale as soon as they change.
+const CACHE_EXTENSIONS: [&[u8; 4]; 2] = [b"TREE", b"UNTR"];
+/// Extensions holding byte offsets into the file, which are dropped since rewriting the entries
+/// would invalidate them.
+const OFFSET_EXTENSIONS: [&[u8; 4]; 2] = [b"EOIE", b"IEOT"];
+
+/// An index extension, kept as raw bytes so that it is written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub signature: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// The staging area stored in `.git/index`: entries sorted by path and stage, followed by
+/// extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    version: u32,
+    format: ObjectFormat,
+    entries: Vec<IndexEntry>,
+    extensions: Vec<Extension>,
+    /// Modification time of the file the index was read from, if any.
+    timestamp: Option<IndexTime>,
+}
+
+impl Index {
+    pub fn new(format: ObjectFormat) -> Self {
+        Self {
+            version: 2,
+            format,
+            entries: Vec::new(),
+            extensions: Vec::new(),
+            timestamp: None,
+        }
+    }
+
+    /// Reads the index at `path`. A missing file is an empty index.
+    pub fn open(path: &Path, format: ObjectFormat) -> Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => {
+                let mut index = Self::parse(&bytes, format)?;
+                index.timestamp = Some(StatData::from_metadata(&fs::metadata(path)?).mtime);
+
+                Ok(index)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new(format)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(bytes: &[u8], format: ObjectFormat) -> Result<Self> {
+        let id_len = format.id_len();
+        let (content, checksum) = bytes
+            .split_at_checked(bytes.len().saturating_sub(id_len))
+            .filter(|(content, _)| content.len() >= 12)
+            .ok_or_else(|| corrupt("file too short"))?;
+
+        // An all-zero checksum is written when `index.skipHash` is set.
+        if checksum.iter().any(|b| *b != 0) && get_sha(format, content).as_bytes() != checksum {
+            return Err(corrupt("bad checksum"));
+        }
+
+        let mut reader = Reader::new(content);
+
+        if reader.take(4)? != SIGNATURE {
+            return Err(corrupt("bad signature"));
+        }
+
+        let version = reader.u32()?;
+
+        if !(2..=4).contains(&version) {
+            return Err(corrupt(&format!("unsupported version {}", version)));
+        }
+
+        let count = reader.u32()?;
+        let mut index = Self {
+            version,
+            // Every entry takes at least 62 bytes, so a bogus count cannot reserve more.
+            entries: Vec::with_capacity((count as usize).min(content.len() / 62)),
+            ..Self::new(format)
+        };
+        let mut previous_path = Vec::new();
+
+        for _ in 0..count {
+            let entry = read_entry(&mut reader, version, format, &mut previous_path)?;
+
+            if let Some(last) = index.entries.last() {
+                if (&last.path, last.stage) >= (&entry.path, entry.stage) {
+                    return Err(corrupt(&format!(
+                        "unordered stage entries for '{}'",
+                        entry.path
+                    )));
+                }
+            }
+
+            index.entries.push(entry);
+        }
+
+        while !reader.is_empty() {
+            let signature: [u8; 4] = reader.take(4)?.try_into().unwrap();
+            let size = reader.u32()? as usize;
+            let data = reader.take(size)?.to_vec();
+
+            if !signature[0].is_ascii_uppercase() {
+                return Err(Error::Generic(format!(
+                    "index uses {} extension, which we do not understand",
+                    String::from_utf8_lossy(&signature)
+                )));
+            }
+
+            if !OFFSET_EXTENSIONS.contains(&&signature) {
+                index.extensions.push(Extension { signature, data });
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Serializes the index, bumping version 2 to 3 when an entry has extended flags.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let version = match self.version {
+            2 if self.entries.iter().any(IndexEntry::has_extended_flags) => 3,
+            version => version,
+        };
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(SIGNATURE);
+        bytes.extend_from_slice(&version.to_be_bytes());
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+
+        let mut previous_path = "";
+
+        for entry in &self.entries {
+            write_entry(&mut bytes, entry, version, previous_path);
+            previous_path = &entry.path;
+        }
+
+        for extension in &self.extensions {
+            bytes.extend_from_slice(&extension.signature);
+            bytes.extend_from_slice(&(extension.data.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&extension.data);
+        }
+
+        let checksum = get_sha(self.format, &bytes);
+        bytes.extend_from_slice(checksum.as_bytes());
+
+        bytes
+    }
+
+    /// Replaces the index at `path` through a lock file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut lock = LockFile::acquire(path)?;
+        lock.write_all(&self.to_bytes())?;
+
+        lock.commit()
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn set_version(&mut self, version: u32) -> Result<()> {
+        if !(2..=4).contains(&version) {
+            return Err(Error::Generic(format!(
+                "index version {} is not supported",
+                version
+            )));
+        }
+
+        self.version = version;
+
+        Ok(())
+    }
+
+    pub fn format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, path: &str, stage: u8) -> Option<&IndexEntry> {
+        self.position(path, stage).ok().map(|i| &self.entries[i])
+    }
+
+    pub fn extensions(&self) -> &[Extension] {
+        &self.extensions
+    }
+
+    /// Whether the work tree file with `metadata` still has the content of `entry`, judging by
+    /// the cached stat data alone. An entry whose file was modified no earlier than the index
+    /// was written is racily clean: it may have changed again within the timestamp resolution,
+    /// so it is never trusted.
+    pub fn is_up_to_date(&self, entry: &IndexEntry, metadata: &Metadata) -> bool {
+        let stat = StatData::from_metadata(metadata);
+
+        entry.stat == stat
+            && entry.mode == mode_from_metadata(metadata)
+            && self.timestamp.is_some_and(|time| stat.mtime < time)
+    }
+
+    /// Inserts `entry`, replacing the entry with the same path and stage.
+    pub fn add(&mut self, entry: IndexEntry) {
+        self.invalidate_caches();
+
+        match self.position(&entry.path, entry.stage) {
+            Ok(i) => self.entries[i] = entry,
+            Err(i) => self.entries.insert(i, entry),
+        }
+    }
+
+    /// Removes every stage of `path`, returning whether it was present.
+    pub fn remove(&mut self, path: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|e| e.path != path);
+
+        if self.entries.len() == len {
+            return false;
+        }
+
+        self.invalidate_caches();
+
+        true
+    }
+
+    fn position(&self, path: &str, stage: u8) -> std::result::Result<usize, usize> {
+        self.entries
+            .binary_search_by(|e| (e.path.as_str(), e.stage).cmp(&(path, stage)))
+    }
+
+    fn invalidate_caches(&mut self) {
+        self.extensions
+            .retain(|e| !CACHE_EXTENSIONS.contains(&&e.signature));
+    }
+}
+
+fn corrupt(reason: &str) -> Error {
+    Error::Generic(format!("index file corrupt: {}", reason))
+}
+
+/// Bounds-checked big-endian reader over the index content.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let (taken, rest) = self
+            .bytes
+            .split_at_checked(len)
+            .ok_or_else(|| corrupt("unexpected end of file"))?;
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Reads up to and including the next NUL byte, returning what came before it.
+    fn take_until_nul(&mut self) -> Result<&'a [u8]> {
+        let nul = self
+            .bytes
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| corrupt("unterminated path"))?;
+        let taken = self.take(nul)?;
+        self.take(1)?;
+
+        Ok(taken)
+    }
+
+    /// Reads the variable-length integer of index v4 path compression.
+    fn varint(&mut self) -> Result<usize> {
+        let mut byte = self.take(1)?[0];
+        let mut value = (byte & 0x7f) as usize;
+
+        while byte & 0x80 != 0 {
+            byte = self.take(1)?[0];
+            value = value
+                .checked_add(1)
+                .and_then(|value| value.checked_mul(128))
+                .ok_or_else(|| corrupt("path prefix length overflow"))?
+                | (byte & 0x7f) as usize;
+        }
+
+        Ok(value)
+    }
+}
+
+fn read_entry(
+    reader: &mut Reader,
+    version: u32,
+    format: ObjectFormat,
+    previous_path: &mut Vec<u8>,
+) -> Result<IndexEntry> {
+    let start = reader.bytes.len();
+    let mut time = || -> Result<IndexTime> {
+        Ok(IndexTime {
+            secs: reader.u32()?,
+            nanos: reader.u32()?,
+        })
+    };
+    let (ctime, mtime) = (time()?, time()?);
+
+    let dev = reader.u32()?;
+    let ino = reader.u32()?;
+    let mode = reader.u32()?;
+    let uid = reader.u32()?;
+    let gid = reader.u32()?;
+    let size = reader.u32()?;
+    let id = ObjectId::from_bytes(reader.take(format.id_len())?)?;
+    let flags = reader.u16()?;
+
+    let extended = match flags & FLAG_EXTENDED {
+        0 => 0,
+        _ if version < 3 => return Err(corrupt("extended flags in a version 2 index")),
+        _ => reader.u16()?,
+    };
+
+    if extended & !(EXTENDED_SKIP_WORKTREE | EXTENDED_INTENT_TO_ADD) != 0 {
+        return Err(corrupt(&format!("unknown entry flags 0x{:04x}", extended)));
+    }
+
+    let path = match version {
+        4 => {
+            let strip = reader.varint()?;
+            let keep = previous_path
+                .len()
+                .checked_sub(strip)
+                .ok_or_else(|| corrupt("path prefix longer than previous path"))?;
+
+            previous_path.truncate(keep);
+            previous_path.extend_from_slice(reader.take_until_nul()?);
+            previous_path.clone()
+        }
+        _ => {
+            let path = reader.take_until_nul()?.to_vec();
+
+            // Entries are padded with 1 to 8 NUL bytes to a multiple of 8, counting the one
+            // terminating the path.
+            let len = start - reader.bytes.len();
+            reader.take((8 - len % 8) % 8)?;
+
+            path
+        }
+    };
+
+    let name_len = (flags & FLAG_NAME_MASK) as usize;
+
+    if name_len != path.len().min(FLAG_NAME_MASK as usize) {
+        return Err(corrupt("path length does not match flags"));
+    }
+
+    Ok(IndexEntry {
+        path: String::from_utf8(path)?,
+        id,
+        mode,
+        stat: StatData {
+            ctime,
+            mtime,
+            dev,
+            ino,
+            uid,
+            gid,
+            size,
+        },
+        stage: ((flags & FLAG_STAGE_MASK) >> 12) as u8,
+        assume_valid: flags & FLAG_ASSUME_VALID != 0,
+        skip_worktree: extended & EXTENDED_SKIP_WORKTREE != 0,
+        intent_to_add: extended & EXTENDED_INTENT_TO_ADD != 0,
+    })
+}
+
+fn write_entry(bytes: &mut Vec<u8>, entry: &IndexEntry, version: u32, previous_path: &str) {
+    let start = bytes.len();
+    let stat = &entry.stat;
+
+    for field in [
+        stat.ctime.secs,
+        stat.ctime.nanos,
+        stat.mtime.secs,
+        stat.mtime.nanos,
+        stat.dev,
+        stat.ino,
+        entry.mode,
+        stat.uid,
+        stat.gid,
+        stat.size,
+    ] {
+        bytes.extend_from_slice(&field.to_be_bytes());
+    }
+
+    bytes.extend_from_slice(entry.id.as_bytes());
+
+    let mut flags = entry.path.len().min(FLAG_NAME_MASK as usize) as u16;
+    flags |= ((entry.stage as u16) << 12) & FLAG_STAGE_MASK;
+
+    if entry.assume_valid {
+        flags |= FLAG_ASSUME_VALID;
+    }
+
+    if entry.has_extended_flags() {
+        flags |= FLAG_EXTENDED;
+    }
+
+    bytes.extend_from_slice(&flags.to_be_bytes());
+
+    if entry.has_extended_flags() {
+        let mut extended = 0;
+
+        if entry.skip_worktree {
+            extended |= EXTENDED_SKIP_WORKTREE;
+        }
+
+        if entry.intent_to_add {
+            extended |= EXTENDED_INTENT_TO_ADD;
+        }
+
+        bytes.extend_from_slice(&extended.to_be_bytes());
+    }
+
+    match version {
+        4 => {
+            let common = previous_path
+                .bytes()
+                .zip(entry.path.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            write_varint(bytes, previous_path.len() - common);
+            bytes.extend_from_slice(&entry.path.as_bytes()[common..]);
+            bytes.push(0);
+        }
+        _ => {
+            bytes.extend_from_slice(entry.path.as_bytes());
+
+            let len = bytes.len() - start;
+            bytes.resize(start + (len + 8) / 8 * 8, 0);
+        }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    let mut encoded = vec![(value & 0x7f) as u8];
+
+    while value >> 7 != 0 {
+        value = (value >> 7) - 1;
+        encoded.push(0x80 | (value & 0x7f) as u8);
+    }
+
+    bytes.extend(encoded.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::entry::MODE_EXECUTABLE;
+
+    fn sample_index(version: u32) -> Index {
+        let id: ObjectId = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391".parse().unwrap();
+        let mut index = Index::new(ObjectFormat::Sha1);
+        index.set_version(version).unwrap();
+
+        for path in ["src/main.rs", "README.md", "src/lib.rs", "src/cmd/mod.rs"] {
+            let mut entry = IndexEntry::new(path, id, entry::MODE_FILE);
+            entry.stat.mtime = IndexTime {
+                secs: 1700000000,
+                nanos: 42,
+            };
+            entry.stat.size = path.len() as u32;
+            index.add(entry);
+        }
+
+        let mut conflict = IndexEntry::new("src/lib.rs", id, MODE_EXECUTABLE);
+        conflict.stage = 2;
+        index.add(conflict);
+
+        index
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for version in 2..=4 {
+            let mut index = sample_index(version);
+            index.extensions.push(Extension {
+                signature: *b"ZZZZ",
+                data: b"opaque".to_vec(),
+            });
+
+            let parsed = Index::parse(&index.to_bytes(), ObjectFormat::Sha1).unwrap();
+            assert_eq!(index, parsed);
+            assert_eq!(
+                vec![
+                    "README.md",
+                    "src/cmd/mod.rs",
+                    "src/lib.rs",
+                    "src/lib.rs",
+                    "src/main.rs"
+                ],
+                parsed.entries().iter().map(|e| &e.path).collect::<Vec<_>>()
+            );
+            assert_eq!(Some(2), parsed.entry("src/lib.rs", 2).map(|e| e.stage));
+        }
+
+        // Extended flags need at least version 3.
+        let mut index = sample_index(2);
+        let mut entry = index.entry("README.md", 0).unwrap().clone();
+        entry.intent_to_add = true;
+        index.add(entry);
+        let parsed = Index::parse(&index.to_bytes(), ObjectFormat::Sha1).unwrap();
+        assert_eq!(3, parsed.version());
+        assert!(parsed.entry("README.md", 0).unwrap().intent_to_add);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let bytes = sample_index(4).to_bytes();
+
+        let mut corrupted = bytes.clone();
+        corrupted[20] ^= 1;
+        assert!(Index::parse(&corrupted, ObjectFormat::Sha1).is_err());
+        assert!(Index::parse(&bytes[..bytes.len() - 1], ObjectFormat::Sha1).is_err());
+
+        // Mandatory extensions we do not know about are refused.
+        let mut index = sample_index(2);
+        index.extensions.push(Extension {
+            signature: *b"link",
+            data: Vec::new(),
+        });
+        assert!(Index::parse(&index.to_bytes(), ObjectFormat::Sha1).is_err());
+
+        // A huge entry count behind a skipped checksum fails instead of aborting on allocation.
+        let mut huge = b"DIRC\0\0\0\x02\xff\xff\xff\xff".to_vec();
+        huge.extend_from_slice(&[0; 20]);
+        assert!(Index::parse(&huge, ObjectFormat::Sha1).is_err());
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 255, 16511, 16512, 1 << 20] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+
+            assert_eq!(value, Reader::new(&bytes).varint().unwrap());
+        }
+
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 128);
+        assert_eq!(vec![0x80, 0x00], bytes);
+
+        assert!(Reader::new(&[0xff; 11]).varint().is_err());
+    }
+}
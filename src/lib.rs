@@ -1,12 +1,21 @@
 pub mod cmd;
+pub mod config;
 pub mod error;
 pub mod hex;
+pub mod ignore;
+pub mod index;
 pub mod obj;
+pub mod pathspec;
+pub mod refs;
+pub mod repository;
+pub mod revision;
 pub mod sha;
 
 pub use crate::error::{Error, Result};
 use cmd::Command;
 
 pub fn run(args: Vec<String>) -> Result<()> {
+    let args = cmd::args::apply_global_options(&args)?;
+
     Command::parse(&args)?.exec()
 }
@@ -9,6 +9,14 @@ impl Blob {
         Self::new(input)
     }
 
+    pub fn from_vec(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         self.0.clone()
     }
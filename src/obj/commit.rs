@@ -1,68 +1,225 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::borrow::Cow;
 
-use crate::hex;
-
-use super::user::User;
+use super::{
+    id::ObjectId,
+    parser::{parse_headers, Header},
+    signature::Signature,
+};
+use crate::{Error, Result};
 
 pub struct Commit {
-    tree_sha: String,
-    parent_sha: Option<String>,
-    msg: String,
-    timestamp: u128,
-    author: User,
-    committer: User,
+    tree_sha: ObjectId,
+    parent_shas: Vec<ObjectId>,
+    /// Raw header values, parsed on access so that commits with odd identities or dates,
+    /// or names in another encoding, still read and re-encode unchanged.
+    author: Vec<u8>,
+    committer: Vec<u8>,
+    /// Headers following `committer` (`encoding`, `mergetag`, `gpgsig` and unknown ones),
+    /// kept in their original order so that re-encoding reproduces the same bytes.
+    extra_headers: Vec<Header>,
+    msg: Vec<u8>,
 }
 
 impl Commit {
     pub fn new(
-        tree_sha: impl ToString,
-        parent_sha: Option<impl ToString>,
+        tree_sha: ObjectId,
+        parent_shas: Vec<ObjectId>,
         msg: impl ToString,
-        author: User,
-        committer: User,
+        author: Signature,
+        committer: Signature,
     ) -> Self {
         Self {
-            tree_sha: tree_sha.to_string(),
-            parent_sha: parent_sha.map(|p| p.to_string()),
-            msg: msg.to_string(),
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis(),
+            tree_sha,
+            parent_shas,
+            author: author.to_string().into_bytes(),
+            committer: committer.to_string().into_bytes(),
+            extra_headers: Vec::new(),
+            msg: format!("{}\n", msg.to_string()).into_bytes(),
+        }
+    }
+
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        let (_, (headers, msg)) = parse_headers(input).map_err(|e| e.into_error(input))?;
+
+        let mut headers = headers.into_iter().peekable();
+
+        let mut parent_shas = Vec::new();
+        let tree_sha = parse_id(&expect_header(headers.next(), "tree")?)?;
+        while let Some((key, _)) = headers.peek() {
+            if key != "parent" {
+                break;
+            }
+
+            parent_shas.push(parse_id(&expect_header(headers.next(), "parent")?)?);
+        }
+        let author = expect_header(headers.next(), "author")?;
+        let committer = expect_header(headers.next(), "committer")?;
+
+        Ok(Self {
+            tree_sha,
+            parent_shas,
             author,
             committer,
-        }
+            extra_headers: headers.collect(),
+            msg: msg.to_vec(),
+        })
+    }
+
+    pub fn tree_sha(&self) -> &ObjectId {
+        &self.tree_sha
+    }
+
+    pub fn parent_shas(&self) -> &[ObjectId] {
+        &self.parent_shas
+    }
+
+    pub fn author(&self) -> Result<Signature> {
+        Signature::parse(&String::from_utf8_lossy(&self.author))
+    }
+
+    pub fn author_bytes(&self) -> &[u8] {
+        &self.author
+    }
+
+    pub fn committer(&self) -> Result<Signature> {
+        Signature::parse(&String::from_utf8_lossy(&self.committer))
+    }
+
+    pub fn committer_bytes(&self) -> &[u8] {
+        &self.committer
+    }
+
+    pub fn extra_headers(&self) -> &[Header] {
+        &self.extra_headers
+    }
+
+    /// Returns the values of all extra headers named `key`, in order.
+    pub fn headers<'a>(&'a self, key: &'a str) -> impl Iterator<Item = Cow<'a, str>> {
+        self.extra_headers
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, value)| String::from_utf8_lossy(value))
+    }
+
+    pub fn encoding(&self) -> Option<Cow<'_, str>> {
+        self.headers("encoding").next()
+    }
+
+    pub fn gpgsig(&self) -> Option<Cow<'_, str>> {
+        self.headers("gpgsig").next()
+    }
+
+    pub fn mergetags(&self) -> Vec<Cow<'_, str>> {
+        self.headers("mergetag").collect()
+    }
+
+    pub fn message(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.msg)
+    }
+
+    pub fn message_bytes(&self) -> &[u8] {
+        &self.msg
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut result = Vec::<u8>::new();
 
-        result.extend_from_slice(b"tree ");
-        result.extend_from_slice(self.tree_sha.as_bytes());
-        result.push(b'\n');
+        push_header(&mut result, "tree", self.tree_sha.to_hex().as_bytes());
 
-        if let Some(parent_sha) = &self.parent_sha {
-            result.extend_from_slice(b"parent ");
-            result.extend_from_slice(parent_sha.as_bytes());
-            result.push(b'\n');
+        for parent_sha in self.parent_shas.iter() {
+            push_header(&mut result, "parent", parent_sha.to_hex().as_bytes());
         }
 
-        result.extend_from_slice(b"author ");
-        result.extend_from_slice(self.author.to_string().as_bytes());
-        result.push(b' ');
-        result.extend_from_slice(self.timestamp.to_string().as_bytes());
-        result.extend_from_slice(b" -0700\n");
+        push_header(&mut result, "author", &self.author);
+        push_header(&mut result, "committer", &self.committer);
 
-        result.extend_from_slice(b"committer ");
-        result.extend_from_slice(self.committer.to_string().as_bytes());
-        result.push(b' ');
-        result.extend_from_slice(self.timestamp.to_string().as_bytes());
-        result.extend_from_slice(b" -0700\n");
+        for (key, value) in self.extra_headers.iter() {
+            push_header(&mut result, key, value);
+        }
 
         result.push(b'\n');
-        result.extend_from_slice(self.msg.as_bytes());
-        result.push(b'\n');
+        result.extend_from_slice(&self.msg);
 
         result
     }
 }
+
+fn expect_header(header: Option<Header>, expected: &str) -> Result<Vec<u8>> {
+    match header {
+        Some((key, value)) if key == expected => Ok(value),
+        _ => Err(Error::ParseObject(format!(
+            "commit is missing {} header",
+            expected
+        ))),
+    }
+}
+
+fn parse_id(value: &[u8]) -> Result<ObjectId> {
+    std::str::from_utf8(value)?.parse()
+}
+
+/// Writes `key value\n`, prefixing every continuation line of `value` with a space.
+pub(super) fn push_header(result: &mut Vec<u8>, key: &str, value: &[u8]) {
+    result.extend_from_slice(key.as_bytes());
+    result.push(b' ');
+
+    for &byte in value {
+        result.push(byte);
+
+        if byte == b'\n' {
+            result.push(b' ');
+        }
+    }
+
+    result.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_round_trip() {
+        let input = b"tree f0e388b3793b68987a5c626e65a3590244df9167\n\
+            parent 92a25908ea9a3f2e1e55da59e6e4ccef25ddbd62\n\
+            parent 12ff8f1984b648645da4a597c2b1050770b8748f\n\
+            author A U Thor <author@example.com> 1700000000 +0100\n\
+            committer C O Mitter <committer@example.com> 1700000100 -0230\n\
+            encoding ISO-8859-1\n\
+            x-custom first\n second\n\
+            gpgsig -----BEGIN PGP SIGNATURE-----\n \n iQEz\n -----END PGP SIGNATURE-----\n\
+            \n\
+            subject\n\nbody \xe9";
+
+        let commit = Commit::parse(input).unwrap();
+
+        assert_eq!(2, commit.parent_shas().len());
+        assert_eq!(-150, commit.committer().unwrap().offset);
+        assert_eq!(Some(Cow::from("ISO-8859-1")), commit.encoding());
+        assert_eq!(
+            vec![Cow::from("first\nsecond")],
+            commit.headers("x-custom").collect::<Vec<_>>()
+        );
+        assert_eq!(input.to_vec(), commit.to_bytes());
+    }
+
+    #[test]
+    fn test_unusual_identities_round_trip() {
+        let input = b"tree f0e388b3793b68987a5c626e65a3590244df9167\n\
+            author Ren\xe9  Latin <rene@example.com> 1700000000 -0000\n\
+            committer Odd <odd@example.com> yesterday +0000\n\
+            encoding ISO-8859-1\n\
+            \n\
+            message\n";
+
+        let commit = Commit::parse(input).unwrap();
+
+        assert_eq!(0, commit.author().unwrap().offset);
+        assert_eq!(
+            b"Ren\xe9  Latin <rene@example.com> 1700000000 -0000",
+            commit.author_bytes()
+        );
+        assert!(commit.committer().is_err());
+        assert_eq!(input.to_vec(), commit.to_bytes());
+    }
+}
@@ -1,13 +1,15 @@
 use super::{parser::parse_raw_object, Object};
-use crate::{error::Error, Result};
+use crate::{error::Error, sha::ObjectFormat, Result};
 
-pub fn decode(input: &[u8]) -> Result<Object> {
-    let (input, raw) = parse_raw_object(input)
-        .map_err(|e| Error::ParseObject(String::from_utf8_lossy(e).into_owned()))?;
+pub fn decode(input: &[u8], format: ObjectFormat) -> Result<Object> {
+    let (rest, raw) = parse_raw_object(input).map_err(|e| e.into_error(input))?;
 
-    if !input.is_empty() {
-        Err(Error::ParseObject(String::from("input not complete")))
-    } else {
-        Object::from_raw(raw)
+    if !rest.is_empty() {
+        return Err(Error::MalformedObject {
+            offset: input.len() - rest.len(),
+            expected: "end of object after as much content as the header declares",
+        });
     }
+
+    Object::from_raw(raw, format)
 }
@@ -0,0 +1,179 @@
+use std::{fmt, str::FromStr};
+
+use crate::{hex, sha::ObjectFormat, Error, Result};
+
+/// Id of the tree with no entries in SHA-1 repositories.
+pub const EMPTY_TREE_SHA1: ObjectId =
+    ObjectId::Sha1(decode_hex("4b825dc642cb6eb9a060e54bf8d69288fbee4904"));
+/// Id of the tree with no entries in SHA-256 repositories.
+pub const EMPTY_TREE_SHA256: ObjectId = ObjectId::Sha256(decode_hex(
+    "6ef19b41225c5369f1c104d45d8d85efa9b057b53b14b4b9b939dd74decc5321",
+));
+/// Id of the blob with no content in SHA-1 repositories.
+pub const EMPTY_BLOB_SHA1: ObjectId =
+    ObjectId::Sha1(decode_hex("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"));
+/// Id of the blob with no content in SHA-256 repositories.
+pub const EMPTY_BLOB_SHA256: ObjectId = ObjectId::Sha256(decode_hex(
+    "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813",
+));
+
+/// A validated object id, sized by the repository's object format.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ObjectId {
+    Sha1([u8; 20]),
+    Sha256([u8; 32]),
+}
+
+impl ObjectId {
+    /// Builds an id from raw bytes, inferring the format from their length.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if let Ok(bytes) = bytes.try_into() {
+            Ok(Self::Sha1(bytes))
+        } else if let Ok(bytes) = bytes.try_into() {
+            Ok(Self::Sha256(bytes))
+        } else {
+            Err(Error::InvalidObjectId(hex::encode(bytes)))
+        }
+    }
+
+    /// The all-zero id, used in ref updates and reflogs to mean "no object".
+    pub fn null(format: ObjectFormat) -> Self {
+        match format {
+            ObjectFormat::Sha1 => Self::Sha1([0; 20]),
+            ObjectFormat::Sha256 => Self::Sha256([0; 32]),
+        }
+    }
+
+    /// The id of the empty tree, which git treats as present in every repository.
+    pub fn empty_tree(format: ObjectFormat) -> Self {
+        match format {
+            ObjectFormat::Sha1 => EMPTY_TREE_SHA1,
+            ObjectFormat::Sha256 => EMPTY_TREE_SHA256,
+        }
+    }
+
+    /// The id of the empty blob, which git treats as present in every repository.
+    pub fn empty_blob(format: ObjectFormat) -> Self {
+        match format {
+            ObjectFormat::Sha1 => EMPTY_BLOB_SHA1,
+            ObjectFormat::Sha256 => EMPTY_BLOB_SHA256,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.as_bytes().iter().all(|b| *b == 0)
+    }
+
+    pub fn format(&self) -> ObjectFormat {
+        match self {
+            ObjectId::Sha1(_) => ObjectFormat::Sha1,
+            ObjectId::Sha256(_) => ObjectFormat::Sha256,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            ObjectId::Sha1(bytes) => bytes,
+            ObjectId::Sha256(bytes) => bytes,
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.as_bytes())
+    }
+}
+
+/// Decodes a lowercase hex id at compile time.
+const fn decode_hex<const N: usize>(hex: &str) -> [u8; N] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            _ => panic!("invalid hex digit"),
+        }
+    }
+
+    let hex = hex.as_bytes();
+    assert!(hex.len() == N * 2, "wrong hex id length");
+
+    let mut bytes = [0; N];
+    let mut i = 0;
+
+    while i < N {
+        bytes[i] = nibble(hex[2 * i]) << 4 | nibble(hex[2 * i + 1]);
+        i += 1;
+    }
+
+    bytes
+}
+
+impl FromStr for ObjectId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let valid_len = [ObjectFormat::Sha1, ObjectFormat::Sha256]
+            .iter()
+            .any(|format| format.hex_len() == s.len());
+
+        if !valid_len || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::InvalidObjectId(s.to_owned()));
+        }
+
+        let bytes = hex::decode(s).map_err(|_| Error::InvalidObjectId(s.to_owned()))?;
+
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Debug for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ObjectId({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::get_sha;
+
+    #[test]
+    fn test_parse_object_id() {
+        let id = "92a25908ea9a3f2e1e55da59e6e4ccef25ddbd62"
+            .parse::<ObjectId>()
+            .unwrap();
+
+        assert_eq!("92a25908ea9a3f2e1e55da59e6e4ccef25ddbd62", id.to_string());
+        assert_eq!(Some(&0x92), id.as_bytes().first());
+        assert!("92a25908".parse::<ObjectId>().is_err());
+        assert!("92a25908ea9a3f2e1e55da59e6e4ccef25ddbdzz"
+            .parse::<ObjectId>()
+            .is_err());
+        assert!("92a25908ea9a3f2e1e55da59e6e4ccef25ddbd\u{e9}"
+            .parse::<ObjectId>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_sha256_object_id() {
+        let id = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            .parse::<ObjectId>()
+            .unwrap();
+
+        assert_eq!(ObjectFormat::Sha256, id.format());
+        assert_eq!(32, id.as_bytes().len());
+    }
+
+    #[test]
+    fn test_empty_ids() {
+        for format in [ObjectFormat::Sha1, ObjectFormat::Sha256] {
+            assert_eq!(get_sha(format, b"tree 0\0"), ObjectId::empty_tree(format));
+            assert_eq!(get_sha(format, b"blob 0\0"), ObjectId::empty_blob(format));
+        }
+    }
+}
@@ -2,40 +2,56 @@ pub mod blob;
 pub mod commit;
 pub mod decode;
 pub mod encode;
+pub mod id;
 pub mod parser;
 pub mod raw;
+pub mod signature;
 pub mod store;
+pub mod tag;
 pub mod tree;
 pub mod user;
 
-use self::{blob::Blob, commit::Commit, raw::RawObject, tree::Tree};
-use crate::{error::Error, Result};
+use self::{blob::Blob, commit::Commit, raw::RawObject, tag::Tag, tree::Tree};
+use crate::{error::Error, sha::ObjectFormat, Result};
 
 pub enum Object {
     Blob(Blob),
     Tree(Tree),
-    Commit(Commit),
+    Commit(Box<Commit>),
+    Tag(Box<Tag>),
 }
 
 impl Object {
-    pub fn from_raw(raw: RawObject) -> Result<Self> {
+    pub fn from_raw(raw: RawObject, format: ObjectFormat) -> Result<Self> {
         let obj = match raw.kind.as_str() {
-            "blob" => Self::from_blob(Blob::parse(&raw.content)),
-            "tree" => Self::from_tree(Tree::parse(&raw.content)?),
+            "blob" => Self::from_blob(Blob::from_vec(raw.content)),
+            "tree" => Self::from_tree(Tree::parse(&raw.content, format)?),
+            "commit" => Self::from_commit(Commit::parse(&raw.content)?),
+            "tag" => Self::from_tag(Tag::parse(&raw.content)?),
             kind => return Err(Error::ParseObject(format!("unknown object kind: {}", kind))),
         };
 
         Ok(obj)
     }
 
-    fn to_raw(&self) -> RawObject {
-        let (kind, content) = match self {
-            Object::Blob(blob) => ("blob", blob.to_bytes()),
-            Object::Tree(tree) => ("tree", tree.to_bytes()),
-            Object::Commit(commit) => ("commit", commit.to_bytes()),
+    pub fn to_raw(&self) -> RawObject {
+        let content = match self {
+            Object::Blob(blob) => blob.to_bytes(),
+            Object::Tree(tree) => tree.to_bytes(),
+            Object::Commit(commit) => commit.to_bytes(),
+            Object::Tag(tag) => tag.to_bytes(),
         };
 
-        RawObject::new(kind, &content)
+        RawObject::new(self.kind(), &content)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Object::Blob(_) => "blob",
+            Object::Tree(_) => "tree",
+            Object::Commit(_) => "commit",
+            Object::Tag(_) => "tag",
+        }
     }
 
     pub fn from_blob(blob: Blob) -> Self {
@@ -69,7 +85,7 @@ impl Object {
     }
 
     pub fn from_commit(commit: Commit) -> Self {
-        Self::Commit(commit)
+        Self::Commit(Box::new(commit))
     }
 
     pub fn as_commit(&self) -> Option<&Commit> {
@@ -82,4 +98,19 @@ impl Object {
     pub fn is_commit(&self) -> bool {
         self.as_commit().is_some()
     }
+
+    pub fn from_tag(tag: Tag) -> Self {
+        Self::Tag(Box::new(tag))
+    }
+
+    pub fn as_tag(&self) -> Option<&Tag> {
+        match self {
+            Self::Tag(tag) => Some(tag),
+            _ => None,
+        }
+    }
+
+    pub fn is_tag(&self) -> bool {
+        self.as_tag().is_some()
+    }
 }
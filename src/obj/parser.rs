@@ -1,6 +1,28 @@
 use super::{raw::RawObject, tree::TreeEntry};
+use crate::Error;
+
+/// Why a parser rejected its input: where it stopped and what it expected to find there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Failure<'a> {
+    /// The input left at the point of failure, a suffix of the input given to the parser.
+    pub remaining: &'a [u8],
+    pub expected: &'static str,
+}
+
+impl Failure<'_> {
+    /// Converts the failure into an error locating it as a byte offset into `input`, the
+    /// input given to the outermost parser.
+    pub fn into_error(self, input: &[u8]) -> Error {
+        Error::MalformedObject {
+            offset: input.len().saturating_sub(self.remaining.len()),
+            expected: self.expected,
+        }
+    }
+}
+
+pub type ParseResult<'a, O> = Result<(&'a [u8], O), Failure<'a>>;
 
-type ParseResult<'a, O> = Result<(&'a [u8], O), &'a [u8]>;
+pub type Header = (String, Vec<u8>);
 
 trait Parser<'a, O> {
     fn parse(&self, input: &'a [u8]) -> ParseResult<'a, O>;
@@ -15,6 +37,13 @@ where
     }
 }
 
+fn fail<'a, O>(remaining: &'a [u8], expected: &'static str) -> ParseResult<'a, O> {
+    Err(Failure {
+        remaining,
+        expected,
+    })
+}
+
 // combinators
 
 fn pair<'a, P1, P2, A, B>(parser1: P1, parser2: P2) -> impl Fn(&'a [u8]) -> ParseResult<'a, (A, B)>
@@ -67,6 +96,14 @@ where
     }
 }
 
+fn right<'a, P1, P2, R1, R2>(parser1: P1, parser2: P2) -> impl Fn(&'a [u8]) -> ParseResult<'a, R2>
+where
+    P1: Parser<'a, R1>,
+    P2: Parser<'a, R2>,
+{
+    map(pair(parser1, parser2), |(_left, right)| right)
+}
+
 fn left<'a, P1, P2, R1, R2>(parser1: P1, parser2: P2) -> impl Fn(&'a [u8]) -> ParseResult<'a, R1>
 where
     P1: Parser<'a, R1>,
@@ -83,12 +120,9 @@ where
         let mut result = Vec::<A>::new();
         let mut current_input = input;
 
-        if let Ok((next_input, first_result)) = parser.parse(current_input) {
-            current_input = next_input;
-            result.push(first_result);
-        } else {
-            return Err(input);
-        }
+        let (next_input, first_result) = parser.parse(current_input)?;
+        current_input = next_input;
+        result.push(first_result);
 
         while let Ok((next_input, subsequent_result)) = parser.parse(current_input) {
             current_input = next_input;
@@ -99,6 +133,42 @@ where
     }
 }
 
+fn zero_or_more<'a, P, A>(parser: P) -> impl Fn(&'a [u8]) -> ParseResult<'a, Vec<A>>
+where
+    P: Parser<'a, A>,
+{
+    move |input| {
+        let mut result = Vec::<A>::new();
+        let mut current_input = input;
+
+        while let Ok((next_input, next_result)) = parser.parse(current_input) {
+            current_input = next_input;
+            result.push(next_result);
+        }
+
+        Ok((current_input, result))
+    }
+}
+
+/// Parses `parser` repeatedly until the input is used up, failing where it fails.
+fn until_end<'a, P, A>(parser: P) -> impl Fn(&'a [u8]) -> ParseResult<'a, Vec<A>>
+where
+    P: Parser<'a, A>,
+{
+    move |input| {
+        let mut result = Vec::<A>::new();
+        let mut current_input = input;
+
+        while !current_input.is_empty() {
+            let (next_input, next_result) = parser.parse(current_input)?;
+            current_input = next_input;
+            result.push(next_result);
+        }
+
+        Ok((current_input, result))
+    }
+}
+
 fn pred<'a, P, F, A>(parser: P, predicate: F) -> impl Fn(&'a [u8]) -> ParseResult<'a, A>
 where
     P: Parser<'a, A>,
@@ -109,12 +179,40 @@ where
             if predicate(&result) {
                 Ok((next_input, result))
             } else {
-                Err(input)
+                fail(input, "different byte")
             }
         })
     }
 }
 
+/// Like [`map`] for conversions that can fail, reporting `expected` at the start of the input.
+fn try_map<'a, P, F, A, B>(
+    parser: P,
+    map_fn: F,
+    expected: &'static str,
+) -> impl Fn(&'a [u8]) -> ParseResult<'a, B>
+where
+    P: Parser<'a, A>,
+    F: Fn(A) -> Option<B>,
+{
+    move |input| {
+        let (next_input, result) = parser.parse(input)?;
+
+        match map_fn(result) {
+            Some(result) => Ok((next_input, result)),
+            None => fail(input, expected),
+        }
+    }
+}
+
+/// Replaces the failure of `parser` with one describing what it parses as a whole.
+fn expect<'a, P, A>(parser: P, expected: &'static str) -> impl Fn(&'a [u8]) -> ParseResult<'a, A>
+where
+    P: Parser<'a, A>,
+{
+    move |input| parser.parse(input).or_else(|_| fail(input, expected))
+}
+
 fn and_then<'a, P1, P2, F, A, B>(
     parser1: P1,
     and_then_fn: F,
@@ -131,30 +229,44 @@ where
     }
 }
 
-fn match_literal(expected: u8) -> impl Fn(&[u8]) -> ParseResult<()> {
-    move |input| match input.get(0) {
+fn match_literal(expected: u8) -> impl Fn(&[u8]) -> ParseResult<'_, ()> {
+    move |input| match input.first() {
         Some(next) if *next == expected => Ok((&input[1..], ())),
-        _ => Err(input),
+        _ => fail(input, byte_name(expected)),
     }
 }
 
-fn take(len: usize) -> impl Fn(&[u8]) -> ParseResult<&[u8]> {
-    move |input| {
-        input
-            .get(0..len)
-            .map(|result| (&input[len..], result))
-            .ok_or(input)
+fn take(len: usize) -> impl Fn(&[u8]) -> ParseResult<'_, &[u8]> {
+    move |input| match input.get(..len) {
+        Some(result) => Ok((&input[len..], result)),
+        None => fail(input, "more bytes"),
+    }
+}
+
+fn take_until(delimiter: u8) -> impl Fn(&[u8]) -> ParseResult<'_, &[u8]> {
+    move |input| match input.iter().position(|c| *c == delimiter) {
+        Some(position) => Ok((&input[position + 1..], &input[..position])),
+        None => fail(input, byte_name(delimiter)),
+    }
+}
+
+fn any_char(input: &[u8]) -> ParseResult<'_, u8> {
+    match input.first() {
+        Some(next) => Ok((&input[1..], *next)),
+        None => fail(input, "more bytes"),
     }
 }
 
-fn any_char(input: &[u8]) -> ParseResult<u8> {
-    input
-        .get(0)
-        .map(|next| (&input[1..], next.to_owned()))
-        .ok_or(input)
+fn byte_name(byte: u8) -> &'static str {
+    match byte {
+        b' ' => "space",
+        b'\0' => "NUL byte",
+        b'\n' => "newline",
+        _ => "delimiter",
+    }
 }
 
-fn identifier(input: &[u8]) -> ParseResult<String> {
+fn identifier(input: &[u8]) -> ParseResult<'_, String> {
     left(
         map(one_or_more(pred(any_char, |c| *c != b' ')), |bytes| {
             String::from_utf8_lossy(&bytes).to_string()
@@ -163,7 +275,7 @@ fn identifier(input: &[u8]) -> ParseResult<String> {
     )(input)
 }
 
-fn null_byte(input: &[u8]) -> ParseResult<String> {
+fn null_byte(input: &[u8]) -> ParseResult<'_, String> {
     left(
         map(one_or_more(pred(any_char, |c| *c != b'\0')), |bytes| {
             String::from_utf8_lossy(&bytes).into_owned()
@@ -172,31 +284,94 @@ fn null_byte(input: &[u8]) -> ParseResult<String> {
     )(input)
 }
 
-fn size(input: &[u8]) -> ParseResult<usize> {
-    map(null_byte, |result| result.parse::<usize>().unwrap())(input)
+fn size(input: &[u8]) -> ParseResult<'_, usize> {
+    try_map(
+        null_byte,
+        |result| match result.bytes().all(|c| c.is_ascii_digit()) {
+            true => result.parse::<usize>().ok(),
+            false => None,
+        },
+        "decimal object size",
+    )(input)
 }
 
-fn content(input: &[u8]) -> ParseResult<(usize, &[u8])> {
+fn content(input: &[u8]) -> ParseResult<'_, (usize, &[u8])> {
     and_then(size, move |len| {
-        map(take(len), move |content| (len, content))
+        map(
+            expect(take(len), "as much content as the header declares"),
+            move |content| (len, content),
+        )
     })(input)
 }
 
-pub fn parse_raw_object(input: &[u8]) -> ParseResult<RawObject> {
-    map(pair(identifier, content), |(kind, (_, content))| {
-        RawObject::new(kind, content)
-    })(input)
+pub fn parse_raw_header(input: &[u8]) -> ParseResult<'_, (String, usize)> {
+    pair(expect(identifier, "object type"), size)(input)
 }
 
-fn tree_entry(input: &[u8]) -> ParseResult<TreeEntry> {
+pub fn parse_raw_object(input: &[u8]) -> ParseResult<'_, RawObject> {
     map(
-        pair3(identifier, null_byte, take(20)),
-        |(mode, name, sha)| TreeEntry::build(mode, name, sha).unwrap(),
+        pair(expect(identifier, "object type"), content),
+        |(kind, (_, content))| RawObject::new(kind, content),
     )(input)
 }
 
-pub fn parse_tree_entries(input: &[u8]) -> ParseResult<Vec<TreeEntry>> {
-    one_or_more(tree_entry)(input)
+fn tree_entry(id_len: usize) -> impl Fn(&[u8]) -> ParseResult<'_, TreeEntry> {
+    move |input| {
+        try_map(
+            pair3(
+                expect(identifier, "tree entry mode"),
+                expect(null_byte, "NUL-terminated entry name"),
+                expect(take(id_len), "object id"),
+            ),
+            |(mode, name, sha)| TreeEntry::build(mode, name, sha).ok(),
+            "known tree entry mode",
+        )(input)
+    }
+}
+
+/// Parses tree content, which must consist of whole entries only.
+pub fn parse_tree_entries(input: &[u8], id_len: usize) -> ParseResult<'_, Vec<TreeEntry>> {
+    until_end(tree_entry(id_len))(input)
+}
+
+fn header_key(input: &[u8]) -> ParseResult<'_, String> {
+    left(
+        map(
+            one_or_more(pred(any_char, |c| *c != b' ' && *c != b'\n')),
+            |bytes| String::from_utf8_lossy(&bytes).into_owned(),
+        ),
+        match_literal(b' '),
+    )(input)
+}
+
+fn header(input: &[u8]) -> ParseResult<'_, Header> {
+    map(
+        pair3(
+            header_key,
+            take_until(b'\n'),
+            zero_or_more(right(match_literal(b' '), take_until(b'\n'))),
+        ),
+        |(key, first_line, continuation_lines)| {
+            let mut value = first_line.to_vec();
+
+            for line in continuation_lines {
+                value.push(b'\n');
+                value.extend_from_slice(line);
+            }
+
+            (key, value)
+        },
+    )(input)
+}
+
+pub fn parse_headers(input: &[u8]) -> ParseResult<'_, (Vec<Header>, &[u8])> {
+    let (input, headers) = zero_or_more(header)(input)?;
+
+    match input.first() {
+        Some(b'\n') => Ok((&[], (headers, &input[1..]))),
+        Some(_) => fail(input, "header or blank line"),
+        None => Ok((input, (headers, input))),
+    }
 }
 
 #[cfg(test)]
@@ -257,10 +432,10 @@ mod tests {
                 TreeEntry::new(
                     TreeEntryMode::BlobExecutable,
                     String::from("your_git.sh"),
-                    String::from("92a25908ea9a3f2e1e55da59e6e4ccef25ddbd62")
+                    "92a25908ea9a3f2e1e55da59e6e4ccef25ddbd62".parse().unwrap()
                 )
             )),
-            tree_entry(&[
+            tree_entry(20)(&[
                 49, 48, 48, 55, 53, 53, 32, 121, 111, 117, 114, 95, 103, 105, 116, 46, 115, 104, 0,
                 146, 162, 89, 8, 234, 154, 63, 46, 30, 85, 218, 89, 230, 228, 204, 239, 37, 221,
                 189, 98,
@@ -277,21 +452,147 @@ mod tests {
                     TreeEntry::new(
                         TreeEntryMode::Tree,
                         String::from("src"),
-                        String::from("f0e388b3793b68987a5c626e65a3590244df9167")
+                        "f0e388b3793b68987a5c626e65a3590244df9167".parse().unwrap()
                     ),
                     TreeEntry::new(
                         TreeEntryMode::BlobExecutable,
                         String::from("your_git.sh"),
-                        String::from("92a25908ea9a3f2e1e55da59e6e4ccef25ddbd62")
+                        "92a25908ea9a3f2e1e55da59e6e4ccef25ddbd62".parse().unwrap()
                     )
                 ]
             )),
-            parse_tree_entries(&[
-                52, 48, 48, 48, 48, 32, 115, 114, 99, 0, 240, 227, 136, 179, 121, 59, 104, 152,
-                122, 92, 98, 110, 101, 163, 89, 2, 68, 223, 145, 103, 49, 48, 48, 55, 53, 53, 32,
-                121, 111, 117, 114, 95, 103, 105, 116, 46, 115, 104, 0, 146, 162, 89, 8, 234, 154,
-                63, 46, 30, 85, 218, 89, 230, 228, 204, 239, 37, 221, 189, 98,
-            ])
+            parse_tree_entries(
+                &[
+                    52, 48, 48, 48, 48, 32, 115, 114, 99, 0, 240, 227, 136, 179, 121, 59, 104, 152,
+                    122, 92, 98, 110, 101, 163, 89, 2, 68, 223, 145, 103, 49, 48, 48, 55, 53, 53,
+                    32, 121, 111, 117, 114, 95, 103, 105, 116, 46, 115, 104, 0, 146, 162, 89, 8,
+                    234, 154, 63, 46, 30, 85, 218, 89, 230, 228, 204, 239, 37, 221, 189, 98,
+                ],
+                20
+            )
         );
     }
+
+    #[test]
+    fn test_headers_parser() {
+        assert_eq!(
+            Ok((
+                "".as_bytes(),
+                (
+                    vec![
+                        (String::from("tree"), b"f0e388b3".to_vec()),
+                        (
+                            String::from("gpgsig"),
+                            b"-----BEGIN\nline\n-----END".to_vec()
+                        ),
+                    ],
+                    "message\n".as_bytes()
+                )
+            )),
+            parse_headers(b"tree f0e388b3\ngpgsig -----BEGIN\n line\n -----END\n\nmessage\n")
+        );
+    }
+
+    #[test]
+    fn test_parse_failures() {
+        let failure = |input: &[u8], result: Failure| match result.into_error(input) {
+            Error::MalformedObject { offset, expected } => (offset, expected),
+            e => panic!("unexpected error {}", e),
+        };
+
+        let input = b"blob 1x\0a";
+        assert_eq!(
+            (5, "decimal object size"),
+            failure(input, parse_raw_object(input).unwrap_err())
+        );
+
+        let input = b"blob 5\0abc";
+        assert_eq!(
+            (7, "as much content as the header declares"),
+            failure(input, parse_raw_object(input).unwrap_err())
+        );
+
+        let mut input = b"100644 a\0".to_vec();
+        input.extend_from_slice(&[0; 20]);
+        input.extend_from_slice(b"100645 b\0");
+        input.extend_from_slice(&[0; 20]);
+        assert_eq!(
+            (29, "known tree entry mode"),
+            failure(&input, parse_tree_entries(&input, 20).unwrap_err())
+        );
+        assert_eq!(
+            (38, "object id"),
+            failure(
+                &input[..40],
+                parse_tree_entries(&input[..40], 20).unwrap_err()
+            )
+        );
+    }
+
+    #[test]
+    fn test_arbitrary_input_does_not_panic() {
+        use std::{env, fs, io::Write, process};
+
+        use flate2::{write::ZlibEncoder, Compression};
+
+        use crate::{
+            obj::{
+                decode::decode,
+                store::{LooseObjectDatabase, ObjectDatabase},
+                Object,
+            },
+            sha::ObjectFormat,
+        };
+
+        let samples: [&[u8]; 6] = [
+            b"tree 5\0",
+            b"commit 0\0tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\nauthor a <b> 1 +0000\n",
+            b"tag 0\0object 4b825dc642cb6eb9a060e54bf8d69288fbee4904\ntype tree\ntagger x",
+            b"blob 18446744073709551616\0",
+            b"tree 99999999999\0",
+            b"blob 18446744073709551615\0",
+        ];
+
+        // Mutated objects are also stored as a loose object and read back through the database.
+        let objects_dir = env::temp_dir().join(format!("parser-fuzz-{}", process::id()));
+        let db = LooseObjectDatabase::new(&objects_dir, ObjectFormat::Sha1);
+        let id = "0123456789abcdef0123456789abcdef01234567".parse().unwrap();
+        let path = objects_dir.join("01/23456789abcdef0123456789abcdef01234567");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        // A fixed linear congruential generator, so that failures are reproducible.
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (state >> 33) as usize
+        };
+
+        for _ in 0..2000 {
+            let mut input = samples[next() % samples.len()].to_vec();
+
+            for _ in 0..next() % 8 {
+                match next() % 3 {
+                    0 if !input.is_empty() => {
+                        let i = next() % input.len();
+                        input[i] = next() as u8;
+                    }
+                    1 => input.insert(next() % (input.len() + 1), next() as u8),
+                    _ => input.truncate(next() % (input.len() + 1)),
+                }
+            }
+
+            let _ = decode(&input, ObjectFormat::Sha1);
+            for kind in ["tree", "commit", "tag"] {
+                let _ = Object::from_raw(RawObject::new(kind, &input), ObjectFormat::Sha1);
+            }
+
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(&input).unwrap();
+            fs::write(&path, encoder.finish().unwrap()).unwrap();
+            let _ = db.read_header(&id);
+            let _ = db.read(&id);
+        }
+
+        fs::remove_dir_all(objects_dir).unwrap();
+    }
 }
@@ -0,0 +1,401 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Error, Result};
+
+/// Identity and timestamp recorded in `author`, `committer` and `tagger` headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// Seconds since the unix epoch.
+    pub time: i64,
+    /// Offset from UTC in minutes.
+    pub offset: i32,
+}
+
+impl Signature {
+    pub fn new(name: impl ToString, email: impl ToString, time: i64, offset: i32) -> Self {
+        Self {
+            name: name.to_string(),
+            email: email.to_string(),
+            time,
+            offset,
+        }
+    }
+
+    /// Creates a signature stamped with the current time in the local time zone.
+    pub fn now(name: impl ToString, email: impl ToString) -> Self {
+        let time = current_time();
+
+        Self::new(name, email, time, local_offset(time))
+    }
+
+    /// Parses the `Name <email> 1700000000 +0100` form.
+    pub fn parse(input: &str) -> Result<Self> {
+        let invalid = || Error::ParseObject(format!("invalid signature {:?}", input));
+
+        let (name, rest) = input.split_once('<').ok_or_else(invalid)?;
+        let (email, date) = rest.split_once('>').ok_or_else(invalid)?;
+        let (time, offset) = date.trim_start().split_once(' ').ok_or_else(invalid)?;
+
+        Ok(Self {
+            name: name.trim_end().to_owned(),
+            email: email.to_owned(),
+            time: time.parse()?,
+            offset: parse_offset(offset).ok_or_else(invalid)?,
+        })
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.abs();
+
+        write!(
+            f,
+            "{} <{}> {} {}{:02}{:02}",
+            self.name,
+            self.email,
+            self.time,
+            sign,
+            offset / 60,
+            offset % 60
+        )
+    }
+}
+
+/// Returns the current time in seconds since the unix epoch.
+pub fn current_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Parses a `+HHMM` / `-HHMM` offset into minutes.
+pub fn parse_offset(input: &str) -> Option<i32> {
+    let (sign, digits) = match input.as_bytes().first()? {
+        b'+' => (1, &input[1..]),
+        b'-' => (-1, &input[1..]),
+        _ => return None,
+    };
+
+    if digits.len() != 4 || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let hours = digits[..2].parse::<i32>().ok()?;
+    let minutes = digits[2..].parse::<i32>().ok()?;
+
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// Parses a date in one of the formats git accepts for `GIT_AUTHOR_DATE`/`GIT_COMMITTER_DATE`:
+/// raw `[@]1700000000 +0100`, ISO 8601 `2023-11-14T22:13:20+01:00` and
+/// RFC 2822 `Tue, 14 Nov 2023 22:13:20 +0100`. Returns seconds and offset in minutes.
+pub fn parse_date(input: &str) -> Option<(i64, i32)> {
+    let input = input.trim();
+
+    parse_raw_date(input)
+        .or_else(|| parse_iso_date(input))
+        .or_else(|| parse_rfc2822_date(input))
+}
+
+fn parse_raw_date(input: &str) -> Option<(i64, i32)> {
+    let input = input.strip_prefix('@').unwrap_or(input);
+
+    let (time, offset) = match input.split_once(' ') {
+        Some((time, offset)) => (time.parse().ok()?, Some(parse_offset(offset)?)),
+        None => (input.parse().ok()?, None),
+    };
+
+    Some((time, offset.unwrap_or_else(|| local_offset(time))))
+}
+
+fn parse_iso_date(input: &str) -> Option<(i64, i32)> {
+    let date = input.get(..10)?;
+    let rest = input[10..].strip_prefix(['T', ' '])?;
+    let time = rest.get(..8)?;
+    let zone = rest[8..].trim_start();
+
+    let mut date_parts = date.split('-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    );
+
+    let local = days_from_civil(year, month, day)? * 86400 + parse_clock(time)?;
+
+    let offset = match zone {
+        "" => None,
+        "Z" => Some(0),
+        zone => Some(parse_offset(&zone.replacen(':', "", 1))?),
+    };
+
+    Some(resolve_offset(local, offset))
+}
+
+fn parse_rfc2822_date(input: &str) -> Option<(i64, i32)> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    let input = input.split_once(", ").map_or(input, |(_, rest)| rest);
+    let mut parts = input.split_whitespace();
+
+    let day = parts.next()?.parse().ok()?;
+    let month = parts.next()?.to_lowercase();
+    let month = MONTHS.iter().position(|m| month.starts_with(m))? as i64 + 1;
+    let year = parts.next()?.parse().ok()?;
+    let time = parts.next()?;
+    let offset = match parts.next() {
+        Some(offset) => Some(parse_offset(offset)?),
+        None => None,
+    };
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let local = days_from_civil(year, month, day)? * 86400 + parse_clock(time)?;
+
+    Some(resolve_offset(local, offset))
+}
+
+/// Parses the dates accepted by `<ref>@{<date>}` and `reflog expire --expire=<date>`: `now`,
+/// `yesterday`, `<n>.<unit>.ago` (or with spaces), a bare `YYYY-MM-DD` at the current time of
+/// day, and anything [`parse_date`] accepts. Returns seconds since the epoch.
+pub fn approxidate(input: &str, now: i64) -> Option<i64> {
+    let input = input.trim();
+    let lower = input.to_lowercase();
+
+    match lower.as_str() {
+        "now" => return Some(now),
+        "yesterday" => return Some(now - 86400),
+        _ => {}
+    }
+
+    let words = lower
+        .split(['.', ' '])
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+
+    if let [count, unit, "ago"] = words.as_slice() {
+        let count = count.parse::<i64>().ok()?;
+        let seconds = match unit.strip_suffix('s').unwrap_or(unit) {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => 3600,
+            "day" => 86400,
+            "week" => 7 * 86400,
+            "month" => 30 * 86400,
+            "year" => 365 * 86400,
+            _ => return None,
+        };
+
+        return Some(now - count * seconds);
+    }
+
+    if let Some((time, _)) = parse_date(input) {
+        return Some(time);
+    }
+
+    let mut date_parts = input.split('-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    );
+
+    if date_parts.next().is_some() {
+        return None;
+    }
+
+    let time_of_day = (now + local_offset(now) as i64 * 60).rem_euclid(86400);
+    let local = days_from_civil(year, month, day)? * 86400 + time_of_day;
+
+    Some(resolve_offset(local, None).0)
+}
+
+/// Formats `time` as `YYYY-MM-DD` in UTC.
+pub fn format_short_date(time: i64) -> String {
+    let (year, month, day) = civil_from_days(time.div_euclid(86400));
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Parses `HH:MM:SS` into seconds since midnight.
+fn parse_clock(input: &str) -> Option<i64> {
+    let mut parts = input.split(':').map(|p| p.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+
+    if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Converts a wall-clock time to a timestamp, assuming the local zone when no offset is given.
+fn resolve_offset(local: i64, offset: Option<i32>) -> (i64, i32) {
+    let offset = offset.unwrap_or_else(|| local_offset(local));
+
+    (local - offset as i64 * 60, offset)
+}
+
+/// Returns the number of days between 1970-01-01 and the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+/// Inverse of [`days_from_civil`], returning the year, month and day.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// Returns the local UTC offset in minutes at `time`, as reported by the C library.
+///
+/// The declarations below follow the `struct tm` layout with `tm_gmtoff` and the 64-bit
+/// `time_t` of Linux and macOS on 64-bit targets, so other targets use UTC.
+#[cfg(all(
+    any(target_os = "linux", target_os = "macos"),
+    target_pointer_width = "64"
+))]
+pub fn local_offset(time: i64) -> i32 {
+    use std::os::raw::{c_char, c_int, c_long};
+
+    #[repr(C)]
+    struct Tm {
+        tm_sec: c_int,
+        tm_min: c_int,
+        tm_hour: c_int,
+        tm_mday: c_int,
+        tm_mon: c_int,
+        tm_year: c_int,
+        tm_wday: c_int,
+        tm_yday: c_int,
+        tm_isdst: c_int,
+        tm_gmtoff: c_long,
+        tm_zone: *const c_char,
+    }
+
+    extern "C" {
+        fn tzset();
+        fn localtime_r(time: *const c_long, result: *mut Tm) -> *mut Tm;
+    }
+
+    let time = time as c_long;
+    let mut tm = std::mem::MaybeUninit::<Tm>::uninit();
+
+    // SAFETY: `localtime_r` only writes into the provided `tm` and returns null on failure.
+    unsafe {
+        tzset();
+
+        if localtime_r(&time, tm.as_mut_ptr()).is_null() {
+            return 0;
+        }
+
+        (tm.assume_init().tm_gmtoff / 60) as i32
+    }
+}
+
+#[cfg(not(all(
+    any(target_os = "linux", target_os = "macos"),
+    target_pointer_width = "64"
+)))]
+pub fn local_offset(_time: i64) -> i32 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_round_trip() {
+        for input in [
+            "A U Thor <author@example.com> 1700000000 +0100",
+            "C O Mitter <committer@example.com> 1112911993 -0230",
+            "Nobody <> 0 +0000",
+        ] {
+            assert_eq!(input, Signature::parse(input).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(Some(60), parse_offset("+0100"));
+        assert_eq!(Some(-150), parse_offset("-0230"));
+        assert_eq!(None, parse_offset("0100"));
+        assert_eq!(None, parse_offset("+100"));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(Some((1700000000, 60)), parse_date("@1700000000 +0100"));
+        assert_eq!(Some((1700000000, 60)), parse_date("1700000000 +0100"));
+        assert_eq!(
+            Some((1700000000, 60)),
+            parse_date("2023-11-14T23:13:20+01:00")
+        );
+        assert_eq!(Some((1700000000, 0)), parse_date("2023-11-14 22:13:20 Z"));
+        assert_eq!(
+            Some((1112911993, 120)),
+            parse_date("Fri, 08 Apr 2005 00:13:13 +0200")
+        );
+        assert_eq!(None, parse_date("yesterday"));
+    }
+
+    #[test]
+    fn test_approxidate() {
+        let now = 1700000000;
+
+        assert_eq!(Some(now), approxidate("now", now));
+        assert_eq!(Some(now - 86400), approxidate("yesterday", now));
+        assert_eq!(Some(now - 2 * 3600), approxidate("2.hours.ago", now));
+        assert_eq!(Some(now - 90 * 86400), approxidate("90 days ago", now));
+        assert_eq!(Some(now - 60), approxidate("1.minute.ago", now));
+        assert_eq!(
+            Some(1112911993),
+            approxidate("2005-04-08 00:13:13 +0200", now)
+        );
+        // A bare day keeps the current time of day, whatever the local zone is.
+        let day = approxidate("2023-11-13", now).unwrap();
+        assert!((now - 2 * 86400..now).contains(&day));
+        assert_eq!(None, approxidate("2.fortnights.ago", now));
+
+        assert_eq!("2023-11-14", format_short_date(now));
+        assert_eq!("1970-01-01", format_short_date(0));
+        assert_eq!("2000-02-29", format_short_date(951782400));
+    }
+}
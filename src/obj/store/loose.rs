@@ -0,0 +1,597 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use flate2::{write::ZlibEncoder, Compression, Decompress, FlushDecompress, Status};
+
+use super::{empty_object_kind, ObjectDatabase, ObjectHeader, ObjectReader, STREAM_BUFFER_SIZE};
+use crate::{
+    config::Config,
+    obj::{id::ObjectId, parser::parse_raw_header, Object},
+    sha::{Hasher, ObjectFormat},
+    Error, Result,
+};
+
+/// Zlib-compressed objects stored one per file under `objects/xx/yyyy...`.
+pub struct LooseObjectDatabase {
+    objects_dir: PathBuf,
+    format: ObjectFormat,
+    /// Check that the content read hashes to the requested id.
+    verify: bool,
+    /// Flush written objects to disk before moving them into place.
+    fsync: bool,
+}
+
+impl LooseObjectDatabase {
+    pub fn new(objects_dir: impl Into<PathBuf>, format: ObjectFormat) -> Self {
+        Self {
+            objects_dir: objects_dir.into(),
+            format,
+            verify: true,
+            fsync: false,
+        }
+    }
+
+    /// Applies `starter.verifyObjects`, which can turn off hashing objects on read for speed.
+    /// Git has no such key, so it lives in a section of its own. The zlib stream and the object
+    /// size are checked either way. Also applies `core.fsync`.
+    pub fn with_config(mut self, config: &Config) -> Result<Self> {
+        self.verify = config.get_bool("starter.verifyObjects")?.unwrap_or(true);
+        self.fsync = fsync_loose_objects(config)?;
+
+        Ok(self)
+    }
+
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// Opens the objects in `objects_dir` with the object format and settings from `config`,
+    /// which the caller loads with all of its scopes.
+    pub fn open(objects_dir: impl Into<PathBuf>, config: &Config) -> Result<Self> {
+        Self::new(objects_dir, ObjectFormat::from_config(config)?).with_config(config)
+    }
+
+    pub fn objects_dir(&self) -> &Path {
+        &self.objects_dir
+    }
+
+    fn get_paths_from_sha(&self, sha: &ObjectId) -> (PathBuf, PathBuf) {
+        let sha = sha.to_hex();
+        let dir_path = self.objects_dir.join(&sha[..2]);
+        let file_path = dir_path.join(&sha[2..]);
+
+        (dir_path, file_path)
+    }
+
+    /// Creates a new temporary file in the objects directory. A name left behind by a crashed
+    /// process with the same pid is skipped rather than overwritten.
+    fn create_temp_object(&self) -> Result<(PathBuf, File)> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        loop {
+            let path = self.objects_dir.join(format!(
+                "tmp_obj_{}_{}",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl ObjectDatabase for LooseObjectDatabase {
+    fn format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    fn contains(&self, id: &ObjectId) -> bool {
+        self.get_paths_from_sha(id).1.is_file() || empty_object_kind(id).is_some()
+    }
+
+    fn read_stream(&self, id: &ObjectId) -> Result<ObjectReader<'_>> {
+        let (_, file_path) = self.get_paths_from_sha(id);
+        let file = match File::open(file_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return ObjectReader::empty_object(id)
+                    .ok_or_else(|| Error::ObjectNotFound(id.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut reader = BufReader::new(Inflater::new(BufReader::new(file)));
+
+        let mut header = Vec::new();
+        reader
+            .read_until(b'\0', &mut header)
+            .map_err(|e| stream_error(id, e))?;
+
+        let (_, (kind, size)) = parse_raw_header(&header).map_err(|e| e.into_error(&header))?;
+
+        let hasher = self.verify.then(|| {
+            let mut hasher = Hasher::new(id.format());
+            hasher.update(&header);
+            hasher
+        });
+
+        Ok(ObjectReader::new(
+            ObjectHeader { kind, size },
+            ContentReader {
+                inner: reader,
+                id: *id,
+                remaining: size as u64,
+                hasher,
+                checked: false,
+            },
+        ))
+    }
+
+    fn ids_with_prefix(&self, prefix: &str) -> Result<Vec<ObjectId>> {
+        let (dir_name, rest) = prefix.split_at(2);
+
+        let entries = match fs::read_dir(self.objects_dir.join(dir_name)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut ids = Vec::new();
+
+        for entry in entries {
+            let file_name = entry?.file_name();
+            let file_name = match file_name.to_str() {
+                Some(name) if name.starts_with(rest) => name,
+                _ => continue,
+            };
+
+            if let Ok(id) = format!("{}{}", dir_name, file_name).parse::<ObjectId>() {
+                if id.format() == self.format {
+                    ids.push(id);
+                }
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Hashes and compresses the content in chunks into a temporary file, then moves it
+    /// into place, so that the content never has to fit in memory and a crash never leaves
+    /// a partial object behind. Objects that already exist are left untouched.
+    fn write_stream(&self, kind: &str, size: u64, reader: &mut dyn Read) -> Result<ObjectId> {
+        let header = format!("{} {}\0", kind, size);
+
+        let mut hasher = Hasher::new(self.format);
+        hasher.update(header.as_bytes());
+
+        fs::create_dir_all(&self.objects_dir)?;
+        let (temp_path, file) = self.create_temp_object()?;
+
+        let result = (|| {
+            let mut zlib_encoder = ZlibEncoder::new(file, Compression::default());
+            zlib_encoder.write_all(header.as_bytes())?;
+
+            let mut buf = vec![0; STREAM_BUFFER_SIZE];
+            let mut written = 0;
+
+            loop {
+                let len = reader.read(&mut buf)?;
+
+                if len == 0 {
+                    break;
+                }
+
+                hasher.update(&buf[..len]);
+                zlib_encoder.write_all(&buf[..len])?;
+                written += len as u64;
+            }
+
+            if written != size {
+                return Err(Error::Generic(format!(
+                    "expected {} bytes of content but read {}",
+                    size, written
+                )));
+            }
+
+            let file = zlib_encoder.finish()?;
+            if self.fsync {
+                file.sync_all()?;
+            }
+
+            let mut permissions = file.metadata()?.permissions();
+            permissions.set_readonly(true);
+            file.set_permissions(permissions)?;
+
+            let sha = hasher.finalize();
+            let (dir_path, file_path) = self.get_paths_from_sha(&sha);
+
+            // Another writer may have stored the same object in the meantime.
+            if !file_path.is_file() {
+                fs::create_dir_all(dir_path)?;
+                fs::rename(&temp_path, file_path)?;
+            }
+
+            Ok(sha)
+        })();
+
+        // Only left behind on failure or when the object already existed.
+        let _ = fs::remove_file(&temp_path);
+
+        result
+    }
+
+    /// Hashes the object first to skip compressing objects that already exist.
+    fn write(&self, object: &Object) -> Result<ObjectId> {
+        let raw = object.to_raw();
+
+        let mut hasher = Hasher::new(self.format);
+        hasher.update(format!("{} {}\0", raw.kind, raw.size).as_bytes());
+        hasher.update(&raw.content);
+        let sha = hasher.finalize();
+
+        if self.get_paths_from_sha(&sha).1.is_file() {
+            return Ok(sha);
+        }
+
+        self.write_stream(&raw.kind, raw.size as u64, &mut raw.content.as_slice())
+    }
+}
+
+/// Whether `core.fsync` asks for loose objects to be flushed. It lists components to add,
+/// or to remove when prefixed with `-`, starting from git's default, which leaves out loose
+/// objects; `none` clears the list. Without it, `core.fsyncObjectFiles` decides.
+fn fsync_loose_objects(config: &Config) -> Result<bool> {
+    let components = match config.get("core.fsync") {
+        Some(components) => components,
+        None => return Ok(config.get_bool("core.fsyncObjectFiles")?.unwrap_or(false)),
+    };
+
+    let mut fsync = false;
+
+    for component in components.split(',').map(str::trim) {
+        let (enable, name) = match component.strip_prefix('-') {
+            Some(name) => (false, name),
+            None => (true, component),
+        };
+
+        match name {
+            "none" => fsync = false,
+            "loose-object" | "objects" | "committed" | "added" | "all" => fsync = enable,
+            // Like git, ignore components it does not know.
+            _ => {}
+        }
+    }
+
+    Ok(fsync)
+}
+
+/// Inflates a zlib stream. Unlike the `flate2` readers, fails when the input ends before the
+/// stream does instead of treating that as the end of the content.
+struct Inflater<R> {
+    input: R,
+    decompress: Decompress,
+    finished: bool,
+}
+
+impl<R: BufRead> Inflater<R> {
+    fn new(input: R) -> Self {
+        Self {
+            input,
+            decompress: Decompress::new(true),
+            finished: false,
+        }
+    }
+
+    /// Whether any input follows the end of the zlib stream.
+    fn has_trailing_input(&mut self) -> io::Result<bool> {
+        Ok(!self.input.fill_buf()?.is_empty())
+    }
+}
+
+impl<R: BufRead> Read for Inflater<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.finished && !buf.is_empty() {
+            let input = self.input.fill_buf()?;
+            let at_eof = input.is_empty();
+
+            let (total_in, total_out) = (self.decompress.total_in(), self.decompress.total_out());
+            let status = self
+                .decompress
+                .decompress(input, buf, FlushDecompress::None)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let consumed = (self.decompress.total_in() - total_in) as usize;
+            let produced = (self.decompress.total_out() - total_out) as usize;
+
+            self.input.consume(consumed);
+            self.finished = status == Status::StreamEnd;
+
+            if produced > 0 {
+                return Ok(produced);
+            }
+
+            if at_eof && !self.finished {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            if consumed == 0 && !self.finished {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "zlib stream makes no progress",
+                ));
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+/// Reads the content of a loose object following its header. On reaching the size declared
+/// by the header, checks that the stream ends there, that nothing follows it in the file and,
+/// if given a hasher, that the object hashes to `id`.
+struct ContentReader<R> {
+    inner: BufReader<Inflater<R>>,
+    id: ObjectId,
+    remaining: u64,
+    hasher: Option<Hasher>,
+    checked: bool,
+}
+
+impl<R: BufRead> ContentReader<R> {
+    fn check_end(&mut self) -> io::Result<()> {
+        if self.checked {
+            return Ok(());
+        }
+        self.checked = true;
+
+        let mut extra = [0; 1];
+        if self
+            .inner
+            .read(&mut extra)
+            .map_err(|e| stream_error(&self.id, e))?
+            > 0
+        {
+            return Err(corrupt(
+                &self.id,
+                "the content to end at the size in the header",
+                "more content",
+            ));
+        }
+
+        if self.inner.get_mut().has_trailing_input()? {
+            return Err(corrupt(
+                &self.id,
+                "the file to end with the zlib stream",
+                "trailing garbage",
+            ));
+        }
+
+        if let Some(hasher) = self.hasher.take() {
+            let actual = hasher.finalize();
+
+            if actual != self.id {
+                return Err(corrupt(
+                    &self.id,
+                    format!("hash {}", self.id),
+                    format!("hash {}", actual),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ContentReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            self.check_end()?;
+            return Ok(0);
+        }
+
+        let len = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let read = self
+            .inner
+            .read(&mut buf[..len])
+            .map_err(|e| stream_error(&self.id, e))?;
+
+        if read == 0 && len > 0 {
+            return Err(corrupt(
+                &self.id,
+                format!("{} more bytes of content", self.remaining),
+                "the end of the zlib stream",
+            ));
+        }
+
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..read]);
+        }
+        self.remaining -= read as u64;
+
+        Ok(read)
+    }
+}
+
+/// Reports a broken zlib stream as a corrupt object.
+fn stream_error(id: &ObjectId, e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            corrupt(id, "the rest of the zlib stream", "the end of the file")
+        }
+        io::ErrorKind::InvalidData => corrupt(id, "a valid zlib stream", e),
+        _ => e,
+    }
+}
+
+fn corrupt(id: &ObjectId, expected: impl ToString, actual: impl ToString) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        Error::CorruptObject {
+            id: *id,
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{
+        config::Scope,
+        obj::{blob::Blob, Object},
+    };
+
+    #[test]
+    fn test_write_and_read() {
+        let objects_dir = env::temp_dir().join(format!("loose-odb-test-{}", process::id()));
+        let db = LooseObjectDatabase::new(&objects_dir, ObjectFormat::Sha1);
+
+        // Temporary files left by a crashed process with the same pid are not overwritten.
+        fs::create_dir_all(&objects_dir).unwrap();
+        let stale = (0..16)
+            .map(|n| objects_dir.join(format!("tmp_obj_{}_{}", process::id(), n)))
+            .collect::<Vec<_>>();
+        for path in &stale {
+            fs::write(path, "stale").unwrap();
+        }
+
+        let id = db
+            .write(&Object::from_blob(Blob::new(b"what is up, doc?")))
+            .unwrap();
+
+        for path in stale {
+            assert_eq!("stale", fs::read_to_string(&path).unwrap());
+            fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!("bd9dbf5aae1a3862dd1526723246b20206e5fc37", id.to_string());
+        let path = objects_dir.join("bd/9dbf5aae1a3862dd1526723246b20206e5fc37");
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+        assert!(db.contains(&id));
+
+        // Writing an existing object leaves it alone, even with a streamed write.
+        let content = b"what is up, doc?";
+        assert_eq!(id, db.write_stream("blob", 16, &mut &content[..]).unwrap());
+        assert_eq!(
+            id,
+            db.write(&Object::from_blob(Blob::new(content))).unwrap()
+        );
+        assert_eq!(1, fs::read_dir(&objects_dir).unwrap().count());
+        assert_eq!(16, db.read_header(&id).unwrap().size);
+        assert_eq!(
+            Some(b"what is up, doc?".as_slice()),
+            db.read(&id).unwrap().as_blob().map(|b| b.as_bytes())
+        );
+
+        let missing = "0000000000000000000000000000000000000001".parse().unwrap();
+        assert!(matches!(db.read(&missing), Err(Error::ObjectNotFound(_))));
+        assert!(matches!(
+            db.read_header(&missing),
+            Err(Error::ObjectNotFound(_))
+        ));
+
+        let empty_tree = ObjectId::empty_tree(ObjectFormat::Sha1);
+        assert!(db.contains(&empty_tree));
+        assert!(db
+            .read(&empty_tree)
+            .unwrap()
+            .as_tree()
+            .unwrap()
+            .entries()
+            .is_empty());
+
+        fs::remove_dir_all(objects_dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_objects() {
+        let objects_dir = env::temp_dir().join(format!("loose-odb-corrupt-{}", process::id()));
+        let mut db = LooseObjectDatabase::new(&objects_dir, ObjectFormat::Sha1);
+
+        let id = db.write(&Object::from_blob(Blob::new(b"hello\n"))).unwrap();
+        let other = db.write(&Object::from_blob(Blob::new(b"world\n"))).unwrap();
+        let (_, path) = db.get_paths_from_sha(&id);
+        let (_, other_path) = db.get_paths_from_sha(&other);
+        let compressed = fs::read(&path).unwrap();
+
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        let is_corrupt = |db: &LooseObjectDatabase| matches!(db.read(&id), Err(Error::CorruptObject { id: bad, .. }) if bad == id);
+
+        fs::copy(&other_path, &path).unwrap();
+        match db.read(&id) {
+            Err(Error::CorruptObject {
+                expected, actual, ..
+            }) => {
+                assert_eq!(format!("hash {}", id), expected);
+                assert_eq!(format!("hash {}", other), actual);
+            }
+            _ => panic!("expected a hash mismatch"),
+        }
+
+        db.set_verify(false);
+        assert!(db.read(&id).is_ok());
+        db.set_verify(true);
+
+        fs::write(&path, &compressed[..compressed.len() - 3]).unwrap();
+        assert!(is_corrupt(&db));
+
+        fs::write(&path, [compressed.as_slice(), b"junk"].concat()).unwrap();
+        assert!(is_corrupt(&db));
+
+        fs::write(&path, &compressed).unwrap();
+        assert!(db.read(&id).is_ok());
+
+        // Sizes in the header that no content backs are not reserved up front.
+        for size in ["99999999999", "18446744073709551615"] {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(format!("tree {}\0", size).as_bytes())
+                .unwrap();
+            fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+            assert_eq!(size, db.read_header(&id).unwrap().size.to_string());
+            assert!(is_corrupt(&db));
+        }
+
+        fs::remove_dir_all(objects_dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_with_config() {
+        let git_dir = env::temp_dir().join(format!("loose-odb-open-{}", process::id()));
+        fs::create_dir_all(&git_dir).unwrap();
+
+        // Settings from any scope apply, not only those in `<git_dir>/config`.
+        let global = git_dir.join("global-config");
+        fs::write(
+            &global,
+            "[core]\n\tfsync = loose-object\n[starter]\n\tverifyObjects = false\n",
+        )
+        .unwrap();
+        let config = Config::load_file(&global, Scope::Global, Some(&git_dir)).unwrap();
+
+        let db = LooseObjectDatabase::open(git_dir.join("objects"), &config).unwrap();
+        assert_eq!(git_dir.join("objects"), db.objects_dir());
+        assert!(db.fsync);
+        assert!(!db.verify);
+
+        fs::remove_dir_all(git_dir).unwrap();
+    }
+}
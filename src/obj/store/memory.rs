@@ -0,0 +1,182 @@
+use std::{collections::HashMap, io::Read, sync::RwLock};
+
+use super::{empty_object_kind, ObjectDatabase, ObjectHeader, ObjectReader};
+use crate::{
+    obj::id::ObjectId,
+    sha::{Hasher, ObjectFormat},
+    Error, Result,
+};
+
+/// Objects kept in memory, for tests and embedders that don't want to touch the disk.
+#[derive(Default)]
+pub struct MemoryObjectDatabase {
+    format: ObjectFormat,
+    objects: RwLock<HashMap<ObjectId, (String, Vec<u8>)>>,
+}
+
+impl MemoryObjectDatabase {
+    pub fn new(format: ObjectFormat) -> Self {
+        Self {
+            format,
+            objects: RwLock::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ObjectDatabase for MemoryObjectDatabase {
+    fn format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    fn contains(&self, id: &ObjectId) -> bool {
+        self.objects.read().unwrap().contains_key(id) || empty_object_kind(id).is_some()
+    }
+
+    fn read_stream(&self, id: &ObjectId) -> Result<ObjectReader<'_>> {
+        let objects = self.objects.read().unwrap();
+        let (kind, content) = match objects.get(id) {
+            Some(object) => object,
+            None => {
+                return ObjectReader::empty_object(id)
+                    .ok_or_else(|| Error::ObjectNotFound(id.to_string()))
+            }
+        };
+
+        let header = ObjectHeader {
+            kind: kind.clone(),
+            size: content.len(),
+        };
+
+        Ok(ObjectReader::new(
+            header,
+            std::io::Cursor::new(content.clone()),
+        ))
+    }
+
+    fn ids_with_prefix(&self, prefix: &str) -> Result<Vec<ObjectId>> {
+        Ok(self
+            .objects
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|id| id.to_hex().starts_with(prefix))
+            .copied()
+            .collect())
+    }
+
+    fn write_stream(&self, kind: &str, size: u64, reader: &mut dyn Read) -> Result<ObjectId> {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+
+        if content.len() as u64 != size {
+            return Err(Error::Generic(format!(
+                "expected {} bytes of content but read {}",
+                size,
+                content.len()
+            )));
+        }
+
+        let mut hasher = Hasher::new(self.format);
+        hasher.update(format!("{} {}\0", kind, size).as_bytes());
+        hasher.update(&content);
+        let id = hasher.finalize();
+
+        self.objects
+            .write()
+            .unwrap()
+            .insert(id, (kind.to_owned(), content));
+
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::{blob::Blob, Object};
+
+    #[test]
+    fn test_write_and_read() {
+        let db = MemoryObjectDatabase::default();
+
+        let id = db
+            .write(&Object::from_blob(Blob::new(b"what is up, doc?")))
+            .unwrap();
+
+        assert_eq!("bd9dbf5aae1a3862dd1526723246b20206e5fc37", id.to_string());
+        assert!(db.contains(&id));
+        assert_eq!(
+            ObjectHeader {
+                kind: String::from("blob"),
+                size: 16
+            },
+            db.read_header(&id).unwrap()
+        );
+        assert_eq!(
+            Some(b"what is up, doc?".as_slice()),
+            db.read(&id).unwrap().as_blob().map(|b| b.as_bytes())
+        );
+
+        let missing = "0000000000000000000000000000000000000001".parse().unwrap();
+        assert!(matches!(db.read(&missing), Err(Error::ObjectNotFound(_))));
+        assert!(matches!(
+            db.read_header(&missing),
+            Err(Error::ObjectNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_prefix() {
+        let db = MemoryObjectDatabase::default();
+
+        let id = db
+            .write(&Object::from_blob(Blob::new(b"what is up, doc?")))
+            .unwrap();
+
+        assert_eq!(id, db.resolve_prefix("bd9d").unwrap());
+        assert_eq!(id, db.resolve_prefix("BD9DBF5A").unwrap());
+        assert_eq!("bd9d", db.shortest_unique_prefix(&id, 4).unwrap());
+        assert!(matches!(
+            db.resolve_prefix("bd9"),
+            Err(Error::InvalidObjectId(_))
+        ));
+        assert!(matches!(
+            db.resolve_prefix("0000"),
+            Err(Error::ObjectNotFound(_))
+        ));
+
+        // Write blobs until two of them share a four character prefix.
+        let mut seen = HashMap::new();
+        let (first, second) = (0..)
+            .find_map(|i| {
+                let id = db
+                    .write(&Object::from_blob(Blob::new(i.to_string().as_bytes())))
+                    .unwrap();
+
+                seen.insert(id.to_hex()[..4].to_owned(), id)
+                    .map(|other| (other, id))
+            })
+            .unwrap();
+
+        match db.resolve_prefix(&first.to_hex()[..4]) {
+            Err(Error::AmbiguousObjectId { candidates, .. }) => {
+                assert_eq!(2, candidates.len());
+                assert!(candidates.iter().all(|(_, kind)| kind == "blob"));
+            }
+            _ => panic!("expected an ambiguous object id error"),
+        }
+
+        let abbrev = db.shortest_unique_prefix(&first, 4).unwrap();
+        assert!(abbrev.len() > 4);
+        assert_eq!(first, db.resolve_prefix(&abbrev).unwrap());
+        assert!(!second.to_hex().starts_with(&abbrev));
+    }
+}
@@ -0,0 +1,183 @@
+pub mod loose;
+pub mod memory;
+
+use std::io::{self, Read};
+
+use super::{id::ObjectId, raw::RawObject, Object};
+use crate::{sha::ObjectFormat, Error, Result};
+
+pub use self::{loose::LooseObjectDatabase, memory::MemoryObjectDatabase};
+
+/// Size of the buffers used to stream object content, which also bounds how much is reserved
+/// up front for the size an object header claims.
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Shortest prefix accepted when resolving or printing an abbreviated object id.
+pub const MIN_ABBREV_LEN: usize = 4;
+
+/// Returns the kind of `id` if it is the empty tree or the empty blob, which, like in git,
+/// every database can read even if they were never written.
+pub fn empty_object_kind(id: &ObjectId) -> Option<&'static str> {
+    if *id == ObjectId::empty_tree(id.format()) {
+        Some("tree")
+    } else if *id == ObjectId::empty_blob(id.format()) {
+        Some("blob")
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectHeader {
+    pub kind: String,
+    pub size: usize,
+}
+
+/// Streaming reader over the content of an object, without its header.
+pub struct ObjectReader<'a> {
+    pub header: ObjectHeader,
+    content: Box<dyn Read + 'a>,
+}
+
+impl<'a> ObjectReader<'a> {
+    pub fn new(header: ObjectHeader, content: impl Read + 'a) -> Self {
+        Self {
+            header,
+            content: Box::new(content),
+        }
+    }
+
+    /// A reader for the empty tree or blob, if `id` is one of them.
+    pub fn empty_object(id: &ObjectId) -> Option<Self> {
+        let header = ObjectHeader {
+            kind: empty_object_kind(id)?.to_owned(),
+            size: 0,
+        };
+
+        Some(Self::new(header, io::empty()))
+    }
+}
+
+impl Read for ObjectReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.content.read(buf)
+    }
+}
+
+/// Storage for git objects, addressed by their id.
+pub trait ObjectDatabase {
+    fn format(&self) -> ObjectFormat;
+
+    fn contains(&self, id: &ObjectId) -> bool;
+
+    /// Opens an object for streaming, reading only as much content as the caller consumes.
+    fn read_stream(&self, id: &ObjectId) -> Result<ObjectReader<'_>>;
+
+    /// Writes an object of `kind` whose `size` bytes of content come from `reader`.
+    fn write_stream(&self, kind: &str, size: u64, reader: &mut dyn Read) -> Result<ObjectId>;
+
+    /// Lists the stored objects whose hex id starts with `prefix`, which is lowercase hex
+    /// and at least two characters long.
+    fn ids_with_prefix(&self, prefix: &str) -> Result<Vec<ObjectId>>;
+
+    /// Resolves a full or abbreviated hex object id to the single object it names.
+    fn resolve_prefix(&self, prefix: &str) -> Result<ObjectId> {
+        let hex = prefix.to_ascii_lowercase();
+
+        if hex.len() < MIN_ABBREV_LEN
+            || hex.len() > self.format().hex_len()
+            || !hex.bytes().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(Error::InvalidObjectId(prefix.to_owned()));
+        }
+
+        if hex.len() == self.format().hex_len() {
+            return hex.parse();
+        }
+
+        let mut ids = self.ids_with_prefix(&hex)?;
+
+        match ids.len() {
+            0 => Err(Error::ObjectNotFound(prefix.to_owned())),
+            1 => Ok(ids.remove(0)),
+            _ => {
+                ids.sort();
+
+                let candidates = ids
+                    .iter()
+                    .map(|id| {
+                        let kind = self
+                            .read_header(id)
+                            .map_or_else(|_| String::from("bad object"), |header| header.kind);
+
+                        Ok((self.shortest_unique_prefix(id, hex.len() + 1)?, kind))
+                    })
+                    .collect::<Result<_>>()?;
+
+                Err(Error::AmbiguousObjectId {
+                    prefix: prefix.to_owned(),
+                    candidates,
+                })
+            }
+        }
+    }
+
+    /// Returns the shortest prefix of `id`, at least `min_len` characters long, that no other
+    /// stored object shares.
+    fn shortest_unique_prefix(&self, id: &ObjectId, min_len: usize) -> Result<String> {
+        let hex = id.to_hex();
+        let min_len = min_len.clamp(MIN_ABBREV_LEN, hex.len());
+
+        let len = self
+            .ids_with_prefix(&hex[..min_len])?
+            .iter()
+            .filter(|other| *other != id)
+            .map(|other| {
+                let common = hex
+                    .bytes()
+                    .zip(other.to_hex().bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                common + 1
+            })
+            .fold(min_len, usize::max);
+
+        Ok(hex[..len.min(hex.len())].to_owned())
+    }
+
+    fn read_header(&self, id: &ObjectId) -> Result<ObjectHeader> {
+        self.read_stream(id).map(|reader| reader.header)
+    }
+
+    fn read(&self, id: &ObjectId) -> Result<Object> {
+        let mut reader = self.read_stream(id)?;
+
+        // The header may be corrupt, so let the buffer grow with the content actually read.
+        let mut content = Vec::with_capacity(reader.header.size.min(STREAM_BUFFER_SIZE));
+        reader.read_to_end(&mut content)?;
+
+        if content.len() != reader.header.size {
+            return Err(Error::ParseObject(format!(
+                "object {} is truncated, expected {} bytes but got {}",
+                id,
+                reader.header.size,
+                content.len()
+            )));
+        }
+
+        let raw = RawObject {
+            kind: reader.header.kind,
+            size: content.len(),
+            content,
+        };
+
+        Object::from_raw(raw, id.format())
+    }
+
+    fn write(&self, object: &Object) -> Result<ObjectId> {
+        let raw = object.to_raw();
+
+        self.write_stream(&raw.kind, raw.size as u64, &mut raw.content.as_slice())
+    }
+}
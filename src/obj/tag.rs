@@ -0,0 +1,221 @@
+use std::borrow::Cow;
+
+use super::{
+    commit::push_header,
+    id::ObjectId,
+    parser::{parse_headers, Header},
+    signature::Signature,
+};
+use crate::{Error, Result};
+
+const SIGNATURE_MARKERS: [&str; 4] = [
+    "-----BEGIN PGP SIGNATURE-----",
+    "-----BEGIN PGP MESSAGE-----",
+    "-----BEGIN SSH SIGNATURE-----",
+    "-----BEGIN SIGNED MESSAGE-----",
+];
+
+pub struct Tag {
+    object_sha: ObjectId,
+    target_kind: String,
+    /// Every header, including `object` and `type` and unknown ones, in their original order
+    /// and as raw bytes, so that re-encoding reproduces the same bytes.
+    headers: Vec<Header>,
+    msg: Vec<u8>,
+    signature: Option<Vec<u8>>,
+}
+
+impl Tag {
+    pub fn new(
+        object_sha: ObjectId,
+        target_kind: impl ToString,
+        name: impl ToString,
+        msg: impl ToString,
+        tagger: Signature,
+    ) -> Self {
+        let target_kind = target_kind.to_string();
+        let headers = vec![
+            (String::from("object"), object_sha.to_hex().into_bytes()),
+            (String::from("type"), target_kind.clone().into_bytes()),
+            (String::from("tag"), name.to_string().into_bytes()),
+            (String::from("tagger"), tagger.to_string().into_bytes()),
+        ];
+
+        Self {
+            object_sha,
+            target_kind,
+            headers,
+            msg: format!("{}\n", msg.to_string()).into_bytes(),
+            signature: None,
+        }
+    }
+
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        let (_, (headers, msg)) = parse_headers(input).map_err(|e| e.into_error(input))?;
+
+        let missing = |header| Error::ParseObject(format!("tag is missing {} header", header));
+        let value = |key| {
+            headers
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_slice())
+                .ok_or_else(|| missing(key))
+        };
+
+        let object_sha = std::str::from_utf8(value("object")?)?.parse()?;
+        let target_kind = String::from_utf8(value("type")?.to_vec())?;
+        value("tag")?;
+
+        let mut msg = msg.to_vec();
+        let signature = signature_start(&msg).map(|position| msg.split_off(position));
+
+        Ok(Self {
+            object_sha,
+            target_kind,
+            headers,
+            msg,
+            signature,
+        })
+    }
+
+    pub fn object_sha(&self) -> &ObjectId {
+        &self.object_sha
+    }
+
+    pub fn target_kind(&self) -> &str {
+        &self.target_kind
+    }
+
+    /// Returns the values of all headers named `key`, in order.
+    pub fn headers<'a>(&'a self, key: &'a str) -> impl Iterator<Item = Cow<'a, str>> {
+        self.headers
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, value)| String::from_utf8_lossy(value))
+    }
+
+    pub fn name(&self) -> Cow<'_, str> {
+        self.headers("tag").next().unwrap_or_default()
+    }
+
+    /// Parses the `tagger` header, which old tags do not have.
+    pub fn tagger(&self) -> Option<Result<Signature>> {
+        self.headers("tagger")
+            .next()
+            .map(|tagger| Signature::parse(&tagger))
+    }
+
+    pub fn message(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.msg)
+    }
+
+    pub fn message_bytes(&self) -> &[u8] {
+        &self.msg
+    }
+
+    pub fn signature(&self) -> Option<Cow<'_, str>> {
+        self.signature
+            .as_deref()
+            .map(|signature| String::from_utf8_lossy(signature))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::<u8>::new();
+
+        for (key, value) in self.headers.iter() {
+            push_header(&mut result, key, value);
+        }
+
+        result.push(b'\n');
+        result.extend_from_slice(&self.msg);
+
+        if let Some(signature) = &self.signature {
+            result.extend_from_slice(signature);
+        }
+
+        result
+    }
+}
+
+/// Finds where the signature of a tag message starts, which like in git is the last line
+/// that begins with a signature marker.
+fn signature_start(msg: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    let mut found = None;
+
+    for line in msg.split_inclusive(|&b| b == b'\n') {
+        if SIGNATURE_MARKERS
+            .iter()
+            .any(|marker| line.starts_with(marker.as_bytes()))
+        {
+            found = Some(start);
+        }
+        start += line.len();
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_round_trip() {
+        let input = b"object f0e388b3793b68987a5c626e65a3590244df9167\n\
+            type commit\n\
+            tag v1.0\n\
+            tagger T Agger <tagger@example.com> 1700000000 -0000\n\
+            x-custom first\n second\n\
+            \n\
+            release \xe9\n\
+            -----BEGIN PGP SIGNATURE-----\n\
+            iQEz\n\
+            -----END PGP SIGNATURE-----\n";
+
+        let tag = Tag::parse(input).unwrap();
+
+        assert_eq!("commit", tag.target_kind());
+        assert_eq!("v1.0", tag.name());
+        assert_eq!(1700000000, tag.tagger().unwrap().unwrap().time);
+        assert_eq!(
+            vec![Cow::from("first\nsecond")],
+            tag.headers("x-custom").collect::<Vec<_>>()
+        );
+        assert_eq!(b"release \xe9\n", tag.message_bytes());
+        assert!(tag
+            .signature()
+            .unwrap()
+            .ends_with("-----END PGP SIGNATURE-----\n"));
+        assert_eq!(input.to_vec(), tag.to_bytes());
+
+        assert!(
+            Tag::parse(b"object f0e388b3793b68987a5c626e65a3590244df9167\ntag v1\n\n").is_err()
+        );
+
+        // A marker quoted in the middle of a line does not start a signature.
+        let input = b"object f0e388b3793b68987a5c626e65a3590244df9167\n\
+            type commit\n\
+            tag v1.1\n\
+            \n\
+            quote -----BEGIN PGP SIGNATURE----- here\n\
+            -----BEGIN SSH SIGNATURE-----\n\
+            U1NI\n\
+            -----END SSH SIGNATURE-----\n";
+
+        let tag = Tag::parse(input).unwrap();
+        assert_eq!("quote -----BEGIN PGP SIGNATURE----- here\n", tag.message());
+        assert!(tag
+            .signature()
+            .unwrap()
+            .starts_with("-----BEGIN SSH SIGNATURE-----\n"));
+        assert_eq!(input.to_vec(), tag.to_bytes());
+
+        let unsigned = b"object f0e388b3793b68987a5c626e65a3590244df9167\n\
+            type commit\n\
+            tag v1.2\n\
+            \n\
+            see -----BEGIN PGP SIGNATURE-----\n";
+        assert!(Tag::parse(unsigned).unwrap().signature().is_none());
+    }
+}
@@ -1,10 +1,16 @@
-use super::parser::parse_tree_entries;
-use crate::{hex, Error, Result};
+use std::cmp::Ordering;
 
-#[derive(Debug, PartialEq, Eq)]
+use super::{id::ObjectId, parser::parse_tree_entries};
+use crate::{sha::ObjectFormat, Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TreeEntryMode {
     Blob,
     BlobExecutable,
+    /// A symbolic link, whose blob holds the link target.
+    Symlink,
+    /// A submodule, pointing at a commit in another repository.
+    Gitlink,
     Tree,
 }
 
@@ -13,6 +19,8 @@ impl TreeEntryMode {
         match self {
             TreeEntryMode::Blob => b"100644",
             TreeEntryMode::BlobExecutable => b"100755",
+            TreeEntryMode::Symlink => b"120000",
+            TreeEntryMode::Gitlink => b"160000",
             TreeEntryMode::Tree => b"40000",
         }
     }
@@ -25,6 +33,8 @@ impl TryFrom<String> for TreeEntryMode {
         let mode = match value.as_str() {
             "100644" => Self::Blob,
             "100755" => Self::BlobExecutable,
+            "120000" => Self::Symlink,
+            "160000" => Self::Gitlink,
             "40000" => Self::Tree,
             mode => return Err(Error::Generic(format!("unknown tree entry mode {}", mode))),
         };
@@ -33,19 +43,42 @@ impl TryFrom<String> for TreeEntryMode {
     }
 }
 
+impl TryFrom<u32> for TreeEntryMode {
+    type Error = Error;
+
+    /// Converts the numeric mode of an index entry.
+    fn try_from(value: u32) -> Result<Self> {
+        let mode = match value {
+            0o100644 => Self::Blob,
+            0o100755 => Self::BlobExecutable,
+            0o120000 => Self::Symlink,
+            0o160000 => Self::Gitlink,
+            0o40000 => Self::Tree,
+            mode => {
+                return Err(Error::Generic(format!(
+                    "unknown tree entry mode {:o}",
+                    mode
+                )))
+            }
+        };
+
+        Ok(mode)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct TreeEntry {
     mode: TreeEntryMode,
     name: String,
-    sha: String,
+    sha: ObjectId,
 }
 
 impl TreeEntry {
-    pub fn new(mode: TreeEntryMode, name: impl ToString, sha: impl ToString) -> Self {
+    pub fn new(mode: TreeEntryMode, name: impl ToString, sha: ObjectId) -> Self {
         Self {
             mode,
             name: name.to_string(),
-            sha: sha.to_string(),
+            sha,
         }
     }
 
@@ -53,9 +86,33 @@ impl TreeEntry {
         Ok(Self {
             mode: mode.try_into()?,
             name,
-            sha: hex::encode(sha),
+            sha: ObjectId::from_bytes(sha)?,
         })
     }
+
+    pub fn mode(&self) -> TreeEntryMode {
+        self.mode
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn sha(&self) -> &ObjectId {
+        &self.sha
+    }
+
+    /// Orders entries the way git sorts them in trees: by name bytes, as if the name of a
+    /// subtree ended with `/`. A file `foo.txt` thus comes before a directory `foo`.
+    pub fn cmp_canonical(&self, other: &Self) -> Ordering {
+        self.sort_name().cmp(other.sort_name())
+    }
+
+    fn sort_name(&self) -> impl Iterator<Item = u8> + '_ {
+        let slash = (self.mode == TreeEntryMode::Tree).then_some(b'/');
+
+        self.name.bytes().chain(slash)
+    }
 }
 
 pub struct Tree(Vec<TreeEntry>);
@@ -66,13 +123,37 @@ impl Tree {
     }
 
     pub fn sort_entries(&mut self) {
-        self.0.sort_by(|a, b| a.name.cmp(&b.name));
+        self.0.sort_by(TreeEntry::cmp_canonical);
+    }
+
+    /// Whether the entries are in git's canonical order, without duplicate names.
+    pub fn is_sorted(&self) -> bool {
+        self.0.windows(2).all(|pair| {
+            pair[0].cmp_canonical(&pair[1]) == Ordering::Less && pair[0].name != pair[1].name
+        })
     }
 
-    pub fn parse(input: &[u8]) -> Result<Self> {
-        parse_tree_entries(input)
-            .map_err(|e| Error::ParseObject(String::from_utf8_lossy(e).into_owned()))
-            .map(|(_, tree_entries)| Self::new(tree_entries))
+    /// Parses tree content, rejecting trees whose entries are not in canonical order, since
+    /// their hash could never be reproduced from the entries.
+    pub fn parse(input: &[u8], format: ObjectFormat) -> Result<Self> {
+        let (_, tree_entries) =
+            parse_tree_entries(input, format.id_len()).map_err(|e| e.into_error(input))?;
+        let tree = Self::new(tree_entries);
+
+        match tree.is_sorted() {
+            true => Ok(tree),
+            false => Err(Error::ParseObject(String::from(
+                "tree entries are not sorted or have duplicate names",
+            ))),
+        }
+    }
+
+    pub fn entries(&self) -> &[TreeEntry] {
+        &self.0
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&TreeEntry> {
+        self.0.iter().find(|e| e.name == name)
     }
 
     pub fn entry_names(&self) -> Vec<&str> {
@@ -87,9 +168,63 @@ impl Tree {
             result.push(b' ');
             result.extend_from_slice(tree_entry.name.as_bytes());
             result.push(b'\0');
-            result.extend_from_slice(hex::decode(&tree_entry.sha).unwrap().as_slice());
+            result.extend_from_slice(tree_entry.sha.as_bytes());
         }
 
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_order() {
+        let id = ObjectId::null(ObjectFormat::Sha1);
+        let mut tree = Tree::new(vec![
+            TreeEntry::new(TreeEntryMode::Tree, "foo", id),
+            TreeEntry::new(TreeEntryMode::Blob, "foo-bar", id),
+            TreeEntry::new(TreeEntryMode::Blob, "foo.txt", id),
+            TreeEntry::new(TreeEntryMode::Blob, "foo0", id),
+        ]);
+        assert!(!tree.is_sorted());
+
+        tree.sort_entries();
+        assert_eq!(
+            vec!["foo-bar", "foo.txt", "foo", "foo0"],
+            tree.entry_names()
+        );
+        assert!(tree.is_sorted());
+        assert!(Tree::parse(&tree.to_bytes(), ObjectFormat::Sha1).is_ok());
+        assert!(Tree::parse(b"", ObjectFormat::Sha1)
+            .unwrap()
+            .entries()
+            .is_empty());
+        assert!(Tree::parse(b"100644 a\0short", ObjectFormat::Sha1).is_err());
+
+        let mut bytes = b"120000 link\0".to_vec();
+        bytes.extend_from_slice(id.as_bytes());
+        bytes.extend_from_slice(b"160000 sub\0");
+        bytes.extend_from_slice(id.as_bytes());
+        let modes = Tree::parse(&bytes, ObjectFormat::Sha1)
+            .unwrap()
+            .entries()
+            .iter()
+            .map(TreeEntry::mode)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![TreeEntryMode::Symlink, TreeEntryMode::Gitlink], modes);
+
+        let unsorted = Tree::new(vec![
+            TreeEntry::new(TreeEntryMode::Blob, "b", id),
+            TreeEntry::new(TreeEntryMode::Blob, "a", id),
+        ]);
+        assert!(Tree::parse(&unsorted.to_bytes(), ObjectFormat::Sha1).is_err());
+
+        let duplicate = Tree::new(vec![
+            TreeEntry::new(TreeEntryMode::Blob, "a", id),
+            TreeEntry::new(TreeEntryMode::Tree, "a", id),
+        ]);
+        assert!(!duplicate.is_sorted());
+    }
+}
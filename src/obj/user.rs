@@ -1,19 +1,209 @@
+use std::{env, fmt, fs};
+
+use super::signature::{parse_date, Signature};
+use crate::{config::Config, Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Author,
+    Committer,
+}
+
+impl Role {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Role::Author => "author",
+            Role::Committer => "committer",
+        }
+    }
+
+    fn env_var_name(&self, field: &str) -> String {
+        format!("GIT_{}_{}", self.as_str().to_uppercase(), field)
+    }
+}
+
+/// Reads a variable from the process environment, treating an empty value as unset.
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
 pub struct User {
     pub name: String,
     pub email: String,
 }
 
-impl Default for User {
-    fn default() -> Self {
+impl User {
+    /// Resolves the identity for `role` the way git does: `GIT_<ROLE>_NAME`/`GIT_<ROLE>_EMAIL`,
+    /// then `<role>.name`/`<role>.email`, then `user.name`/`user.email` from config, and finally
+    /// `EMAIL` for the email address.
+    pub fn resolve(role: Role, config: &Config) -> Result<Self> {
+        Self::resolve_with(role, config, var)
+    }
+
+    /// Like [`User::resolve`], with environment variables read through `env`.
+    fn resolve_with(
+        role: Role,
+        config: &Config,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        match Self::lookup(role, config, env) {
+            (Some(name), Some(email)) => Ok(Self { name, email }),
+            _ => Err(Error::UnknownIdentity(role.as_str())),
+        }
+    }
+
+    /// Like [`User::resolve`], but fills in what is not configured from git's default identity,
+    /// the login name and `<login>@<hostname>`, the way git does where it does not insist on
+    /// an identity, such as for reflogs.
+    pub fn resolve_or_default(role: Role, config: &Config) -> Self {
+        let (name, email) = Self::lookup(role, config, var);
+        let login = || {
+            ["USER", "LOGNAME", "USERNAME"]
+                .into_iter()
+                .find_map(var)
+                .unwrap_or_else(|| String::from("unknown"))
+        };
+
         Self {
-            name: String::from("Nikita Cheremuhin"),
-            email: String::from("nikitadf68@gmail.com"),
+            name: name.unwrap_or_else(login),
+            email: email.unwrap_or_else(|| format!("{}@{}", login(), hostname())),
+        }
+    }
+
+    fn lookup(
+        role: Role,
+        config: &Config,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> (Option<String>, Option<String>) {
+        let from_config = |field: &str| {
+            config
+                .get(&format!("{}.{}", role.as_str(), field))
+                .or_else(|| config.get(&format!("user.{}", field)))
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+
+        let name = env(&role.env_var_name("NAME")).or_else(|| from_config("name"));
+        let email = env(&role.env_var_name("EMAIL"))
+            .or_else(|| from_config("email"))
+            .or_else(|| env("EMAIL"));
+
+        (name, email)
+    }
+
+    /// Resolves the identity for `role` and stamps it with `GIT_<ROLE>_DATE` or the current time.
+    pub fn signature(role: Role, config: &Config) -> Result<Signature> {
+        Self::resolve(role, config)?.stamp(role)
+    }
+
+    /// Like [`User::signature`], with the fallbacks of [`User::resolve_or_default`].
+    pub fn signature_or_default(role: Role, config: &Config) -> Result<Signature> {
+        Self::resolve_or_default(role, config).stamp(role)
+    }
+
+    /// Stamps the identity with `GIT_<ROLE>_DATE` or the current time.
+    fn stamp(self, role: Role) -> Result<Signature> {
+        match var(&role.env_var_name("DATE")) {
+            Some(date) => {
+                let (time, offset) = parse_date(&date).ok_or(Error::InvalidDate(date))?;
+
+                Ok(Signature::new(self.name, self.email, time, offset))
+            }
+            None => Ok(Signature::now(self.name, self.email)),
         }
     }
 }
 
-impl ToString for User {
-    fn to_string(&self) -> String {
-        format!("{} <{}>", self.name, self.email)
+/// The name of this machine, or `localhost` when it cannot be found.
+fn hostname() -> String {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .into_iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .or_else(|| env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| String::from("localhost"))
+}
+
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::config::Scope;
+
+    /// An environment holding only `vars`.
+    fn vars<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let path = env::temp_dir().join(format!("user-test-{}", process::id()));
+        fs::write(
+            &path,
+            "[user]\n\tname = User Name\n\temail = user@example.com\n\
+             [author]\n\tname = Author Name\n",
+        )
+        .unwrap();
+        let config = Config::load_file(&path, Scope::Global, None).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let resolve = |role, env: &[(&str, &str)]| {
+            User::resolve_with(role, &config, vars(env))
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            "Author Name <user@example.com>",
+            resolve(Role::Author, &[("EMAIL", "env@example.com")])
+        );
+        assert_eq!(
+            "User Name <user@example.com>",
+            resolve(Role::Committer, &[("GIT_AUTHOR_NAME", "Env Author")])
+        );
+        assert_eq!(
+            "Env Author <env-author@example.com>",
+            resolve(
+                Role::Author,
+                &[
+                    ("GIT_AUTHOR_NAME", "Env Author"),
+                    ("GIT_AUTHOR_EMAIL", "env-author@example.com"),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_unknown_identity() {
+        let config = Config::default();
+
+        assert!(matches!(
+            User::resolve_with(Role::Author, &config, vars(&[])),
+            Err(Error::UnknownIdentity("author"))
+        ));
+        assert!(matches!(
+            User::resolve_with(
+                Role::Committer,
+                &config,
+                vars(&[("GIT_COMMITTER_NAME", "C")])
+            ),
+            Err(Error::UnknownIdentity("committer"))
+        ));
+
+        let env = [("GIT_COMMITTER_NAME", "C"), ("EMAIL", "c@example.com")];
+        let user = User::resolve_with(Role::Committer, &config, vars(&env)).unwrap();
+        assert_eq!("C <c@example.com>", user.to_string());
     }
 }
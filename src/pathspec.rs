@@ -0,0 +1,199 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::{Error, Result};
+
+/// How a pathspec item matched a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathspecMatch {
+    /// The item names the path itself.
+    Exact,
+    /// The item names a directory containing the path.
+    Leading,
+    /// The item is a glob matching the path.
+    Glob,
+}
+
+/// One path given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathspecItem {
+    /// The argument as given, for messages.
+    pub arg: String,
+    /// Slash-separated path relative to the top of the work tree, empty for the whole tree.
+    pub path: String,
+}
+
+impl PathspecItem {
+    pub fn matches(&self, path: &str) -> Option<PathspecMatch> {
+        if path == self.path {
+            Some(PathspecMatch::Exact)
+        } else if self.path.is_empty()
+            || path
+                .strip_prefix(self.path.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+        {
+            Some(PathspecMatch::Leading)
+        } else if is_glob(&self.path) && glob_matches(self.path.as_bytes(), path.as_bytes()) {
+            Some(PathspecMatch::Glob)
+        } else {
+            None
+        }
+    }
+}
+
+/// Paths given on the command line, resolved against the top of the work tree. An empty
+/// pathspec matches every path.
+#[derive(Debug, Default)]
+pub struct Pathspec {
+    items: Vec<PathspecItem>,
+}
+
+impl Pathspec {
+    /// Resolves `args`, which are relative to `cwd`, to paths inside `work_tree`.
+    pub fn new(work_tree: &Path, cwd: &Path, args: &[String]) -> Result<Self> {
+        let work_tree = work_tree.canonicalize()?;
+        let cwd = cwd.canonicalize()?;
+
+        let items = args
+            .iter()
+            .map(|arg| {
+                let outside = || {
+                    Error::Generic(format!(
+                        "'{}' is outside repository at '{}'",
+                        arg,
+                        work_tree.display()
+                    ))
+                };
+
+                let path = normalize(&cwd.join(arg));
+                let path = path
+                    .strip_prefix(&work_tree)
+                    .map_err(|_| outside())?
+                    .components()
+                    .map(|c| c.as_os_str().to_str().ok_or_else(outside))
+                    .collect::<Result<Vec<_>>>()?
+                    .join("/");
+
+                Ok(PathspecItem {
+                    arg: arg.to_owned(),
+                    path,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { items })
+    }
+
+    pub fn items(&self) -> &[PathspecItem] {
+        &self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.is_empty() || self.items.iter().any(|i| i.matches(path).is_some())
+    }
+
+    /// Whether any path inside the directory `dir` can match, so that walks can skip the rest.
+    pub fn may_match_inside(&self, dir: &str) -> bool {
+        self.is_empty()
+            || self.items.iter().any(|item| {
+                is_glob(&item.path)
+                    || item.matches(dir).is_some()
+                    || item
+                        .path
+                        .strip_prefix(dir)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+
+    /// Fails with the first item whose entry in `matched` is false.
+    pub fn check_matched(&self, matched: &[bool]) -> Result<()> {
+        match self
+            .items
+            .iter()
+            .zip(matched)
+            .find(|(_, matched)| !**matched)
+        {
+            Some((item, _)) => Err(Error::Generic(format!(
+                "pathspec '{}' did not match any files",
+                item.arg
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+/// Matches `text` against a glob where, unlike in config and ref patterns, `*` also matches `/`.
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') => (0..=text.len()).any(|i| glob_matches(&pattern[1..], &text[i..])),
+        Some(b'?') => !text.is_empty() && glob_matches(&pattern[1..], &text[1..]),
+        Some(p) => text.first() == Some(p) && glob_matches(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Resolves `.` and `..` in an absolute path without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    #[test]
+    fn test_pathspec() {
+        let work_tree = env::temp_dir().join(format!("pathspec-test-{}", process::id()));
+        fs::create_dir_all(work_tree.join("src/obj")).unwrap();
+
+        let args = ["obj/", "../README.md", ".", "*.rs"].map(String::from);
+        let pathspec = Pathspec::new(&work_tree, &work_tree.join("src"), &args).unwrap();
+        let paths = pathspec
+            .items()
+            .iter()
+            .map(|i| i.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["src/obj", "README.md", "src", "src/*.rs"], paths);
+
+        let items = pathspec.items();
+        assert_eq!(Some(PathspecMatch::Leading), items[0].matches("src/obj/a"));
+        assert_eq!(None, items[0].matches("src/objects"));
+        assert_eq!(Some(PathspecMatch::Exact), items[1].matches("README.md"));
+        assert_eq!(Some(PathspecMatch::Glob), items[3].matches("src/lib.rs"));
+        assert_eq!(
+            Some(PathspecMatch::Glob),
+            items[3].matches("src/obj/mod.rs")
+        );
+        assert_eq!(None, items[3].matches("src/obj/mod.rs.orig"));
+        assert!(pathspec.may_match_inside("src"));
+        assert!(Pathspec::new(&work_tree, &work_tree, &[String::from("..")]).is_err());
+
+        let pathspec = Pathspec::new(&work_tree, &work_tree, &[String::from("src/obj")]).unwrap();
+        assert!(pathspec.may_match_inside("src"));
+        assert!(!pathspec.may_match_inside("target"));
+        assert!(pathspec.check_matched(&[false]).is_err());
+
+        fs::remove_dir_all(work_tree).unwrap();
+    }
+}
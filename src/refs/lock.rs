@@ -0,0 +1,93 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Result};
+
+/// Exclusive lock on a file, held by creating `<path>.lock`. Content written to the lock
+/// replaces the file on [`LockFile::commit`]; dropping the lock without committing discards it.
+pub struct LockFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<File>,
+}
+
+impl LockFile {
+    pub fn acquire(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => Error::Generic(format!(
+                    "unable to create '{}': file exists, another git process seems to be running",
+                    lock_path.display()
+                )),
+                _ => Error::IoError(e),
+            })?;
+
+        Ok(Self {
+            path,
+            lock_path,
+            file: Some(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_all(&mut self, content: &[u8]) -> Result<()> {
+        match &mut self.file {
+            Some(file) => Ok(file.write_all(content)?),
+            None => Err(Error::Generic(String::from("lock file already closed"))),
+        }
+    }
+
+    /// Moves the new content into place and releases the lock.
+    pub fn commit(mut self) -> Result<()> {
+        let result = match self.file.take() {
+            Some(file) => file.sync_all(),
+            None => Ok(()),
+        }
+        .and_then(|_| fs::rename(&self.lock_path, &self.path));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+
+        Ok(result?)
+    }
+
+    /// Deletes the locked file and releases the lock.
+    pub fn commit_delete(mut self) -> Result<()> {
+        self.file.take();
+
+        let result = match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+        let _ = fs::remove_file(&self.lock_path);
+
+        result
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
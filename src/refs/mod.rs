@@ -0,0 +1,403 @@
+pub mod lock;
+pub mod packed;
+pub mod reflog;
+pub mod transaction;
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use self::{
+    lock::LockFile,
+    packed::PackedRefs,
+    reflog::{append_reflog, ReflogMessage},
+    transaction::Transaction,
+};
+use crate::{obj::id::ObjectId, Error, Result};
+
+/// How many symbolic refs are followed before giving up, as in git.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+/// Prefixes tried, in order, when expanding a short ref name such as `main` or `v1.0`.
+const REF_RULES: [(&str, &str); 6] = [
+    ("", ""),
+    ("refs/", ""),
+    ("refs/tags/", ""),
+    ("refs/heads/", ""),
+    ("refs/remotes/", ""),
+    ("refs/remotes/", "/HEAD"),
+];
+
+/// The content of a ref: either an object id or a `ref: <name>` pointer to another ref.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefValue {
+    Direct(ObjectId),
+    Symbolic(String),
+}
+
+/// A ref under `refs/` with the id it resolves to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub name: String,
+    pub id: ObjectId,
+    /// What an annotated tag peels to, when recorded in `packed-refs`.
+    pub peeled: Option<ObjectId>,
+}
+
+/// Reads a single ref from its loose file or from `packed-refs`, without following symbolic refs.
+pub fn read_ref(git_dir: &Path, name: &str) -> Result<Option<RefValue>> {
+    let path = git_dir.join(name);
+
+    match fs::read_to_string(&path) {
+        Ok(content) => return parse_ref_value(name, &content).map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound || path.is_dir() => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(PackedRefs::open(git_dir)?
+        .get(name)
+        .map(|packed_ref| RefValue::Direct(packed_ref.id)))
+}
+
+fn parse_ref_value(name: &str, content: &str) -> Result<RefValue> {
+    let content = content.trim_end();
+
+    match content.strip_prefix("ref:") {
+        Some(target) => Ok(RefValue::Symbolic(target.trim_start().to_owned())),
+        None => content
+            .parse()
+            .map(RefValue::Direct)
+            .map_err(|_| Error::Generic(format!("invalid ref {}: {}", name, content))),
+    }
+}
+
+/// Follows symbolic refs from `name`, returning the name of the final ref and its id if it exists.
+pub fn resolve_ref(git_dir: &Path, name: &str) -> Result<(String, Option<ObjectId>)> {
+    let mut name = name.to_owned();
+
+    for _ in 0..=MAX_SYMREF_DEPTH {
+        match read_ref(git_dir, &name)? {
+            Some(RefValue::Symbolic(target)) => name = target,
+            Some(RefValue::Direct(id)) => return Ok((name, Some(id))),
+            None => return Ok((name, None)),
+        }
+    }
+
+    Err(Error::Generic(format!("symbolic ref loop at {}", name)))
+}
+
+/// Expands a short name like `main` to the first existing ref it can refer to, returning the
+/// full name of that ref and the id it resolves to.
+pub fn dwim_ref(git_dir: &Path, name: &str) -> Result<Option<(String, ObjectId)>> {
+    for (prefix, suffix) in REF_RULES {
+        let full_name = format!("{}{}{}", prefix, name, suffix);
+
+        if check_ref_format(&full_name, true).is_err() {
+            continue;
+        }
+
+        if let (_, Some(id)) = resolve_ref(git_dir, &full_name)? {
+            return Ok(Some((full_name, id)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Lists the loose and packed refs whose name starts with `prefix`, sorted by name. Loose refs
+/// take precedence over packed ones, and symbolic refs are listed with the id they resolve to.
+pub fn list_refs(git_dir: &Path, prefix: &str) -> Result<Vec<Reference>> {
+    let mut loose = Vec::new();
+    collect_loose_refs(git_dir, &git_dir.join("refs"), &mut loose)?;
+
+    let mut refs = Vec::new();
+
+    for name in &loose {
+        if let (_, Some(id)) = resolve_ref(git_dir, name)? {
+            refs.push(Reference {
+                name: name.to_owned(),
+                id,
+                peeled: None,
+            });
+        }
+    }
+
+    for packed_ref in PackedRefs::open(git_dir)?.refs() {
+        if !loose.contains(&packed_ref.name) {
+            refs.push(Reference {
+                name: packed_ref.name.clone(),
+                id: packed_ref.id,
+                peeled: packed_ref.peeled,
+            });
+        }
+    }
+
+    refs.retain(|r| r.name.starts_with(prefix));
+    refs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(refs)
+}
+
+fn collect_loose_refs(git_dir: &Path, dir: &Path, names: &mut Vec<String>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_loose_refs(git_dir, &path, names)?;
+            continue;
+        }
+
+        let name = match path.strip_prefix(git_dir).ok().and_then(Path::to_str) {
+            Some(name) => name.replace(std::path::MAIN_SEPARATOR, "/"),
+            None => continue,
+        };
+
+        if check_ref_format(&name, false).is_ok() {
+            names.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Points `name` at `id` in a single-ref transaction, logging the update with `reflog`.
+pub fn write_ref(
+    git_dir: &Path,
+    name: &str,
+    id: &ObjectId,
+    reflog: Option<ReflogMessage>,
+) -> Result<()> {
+    let mut transaction = Transaction::new(git_dir);
+    transaction.update(name, *id, None)?;
+
+    if let Some(reflog) = reflog {
+        transaction.reflog(reflog);
+    }
+
+    transaction.commit()
+}
+
+/// Makes `name` a symbolic ref pointing at the ref `target`. When `target` exists the switch is
+/// logged with `reflog`, even if `name` resolved to the same id before.
+pub fn write_symbolic_ref(
+    git_dir: &Path,
+    name: &str,
+    target: &str,
+    reflog: Option<&ReflogMessage>,
+) -> Result<()> {
+    validate_ref_name(name)?;
+    validate_ref_name(target)?;
+
+    let mut lock = LockFile::acquire(git_dir.join(name))?;
+    let old = resolve_ref(git_dir, name)?.1;
+
+    lock.write_all(format!("ref: {}\n", target).as_bytes())?;
+    lock.commit()?;
+
+    if let (Some(reflog), (_, Some(new))) = (reflog, resolve_ref(git_dir, target)?) {
+        let old = old.unwrap_or_else(|| ObjectId::null(new.format()));
+        append_reflog(git_dir, name, old, new, reflog)?;
+    }
+
+    Ok(())
+}
+
+/// Removes `name` from both the loose refs and `packed-refs`, returning whether it existed.
+pub fn delete_ref(git_dir: &Path, name: &str) -> Result<bool> {
+    let existed = read_ref(git_dir, name)?.is_some();
+
+    let mut transaction = Transaction::new(git_dir);
+    transaction.delete(name, None)?;
+    transaction.commit()?;
+
+    Ok(existed)
+}
+
+/// Removes the directories left empty below `root` after deleting a loose ref or its reflog.
+fn remove_empty_parents(root: &Path, path: &Path) {
+    let mut dir = path.parent().map(PathBuf::from);
+
+    while let Some(current) = dir {
+        if !current.starts_with(root) || current == root || fs::remove_dir(&current).is_err() {
+            break;
+        }
+
+        dir = current.parent().map(PathBuf::from);
+    }
+}
+
+/// Checks that `name` is a root ref such as `HEAD` or `ORIG_HEAD`, or a valid name under `refs/`.
+pub fn validate_ref_name(name: &str) -> Result<()> {
+    let is_root_ref = !name.is_empty() && name.bytes().all(|c| c.is_ascii_uppercase() || c == b'_');
+
+    if is_root_ref || (name.starts_with("refs/") && check_ref_format(name, false).is_ok()) {
+        Ok(())
+    } else {
+        Err(Error::InvalidRefName(name.to_owned()))
+    }
+}
+
+/// Validates a ref name following the rules of `git check-ref-format`. Unless `allow_onelevel`
+/// is set the name must contain at least one `/`.
+pub fn check_ref_format(name: &str, allow_onelevel: bool) -> Result<()> {
+    let invalid = || Err(Error::InvalidRefName(name.to_owned()));
+
+    if name.is_empty()
+        || name == "@"
+        || name.contains("..")
+        || name.contains("@{")
+        || name.ends_with('.')
+        || (!allow_onelevel && !name.contains('/'))
+    {
+        return invalid();
+    }
+
+    let forbidden = |c: u8| c < 0x20 || c == 0x7f || b" ~^:?*[\\".contains(&c);
+    if name.bytes().any(forbidden) {
+        return invalid();
+    }
+
+    for component in name.split('/') {
+        if component.is_empty() || component.starts_with('.') || component.ends_with(".lock") {
+            return invalid();
+        }
+    }
+
+    Ok(())
+}
+
+/// Strips the well-known prefix from a full ref name, as in `refname:short`.
+pub fn shorten_ref_name(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn test_resolve_ref() {
+        let git_dir = env::temp_dir().join(format!("refs-test-{}", process::id()));
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+
+        let main = "bd9dbf5aae1a3862dd1526723246b20206e5fc37";
+        let tag = "45b983be36b73c0788dc9cbcb76cbb80fc7bb057";
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(git_dir.join("refs/heads/main"), format!("{}\n", main)).unwrap();
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted\n{} refs/tags/v1.0\n^{}\n",
+                tag, main
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            (String::from("refs/heads/main"), Some(main.parse().unwrap())),
+            resolve_ref(&git_dir, "HEAD").unwrap()
+        );
+        assert_eq!(
+            Some((String::from("refs/tags/v1.0"), tag.parse().unwrap())),
+            dwim_ref(&git_dir, "v1.0").unwrap()
+        );
+        assert_eq!(None, dwim_ref(&git_dir, "missing").unwrap());
+
+        fs::write(git_dir.join("refs/heads/main"), "ref: HEAD\n").unwrap();
+        assert!(resolve_ref(&git_dir, "HEAD").is_err());
+
+        fs::remove_dir_all(git_dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_list_and_delete() {
+        let git_dir = env::temp_dir().join(format!("refs-write-test-{}", process::id()));
+        fs::create_dir_all(git_dir.join("refs")).unwrap();
+
+        let main = "bd9dbf5aae1a3862dd1526723246b20206e5fc37".parse().unwrap();
+        let tag: ObjectId = "45b983be36b73c0788dc9cbcb76cbb80fc7bb057".parse().unwrap();
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!(
+                "{} refs/tags/v1.0\n^{}\n{} refs/heads/main\n",
+                tag, main, tag
+            ),
+        )
+        .unwrap();
+
+        write_ref(&git_dir, "refs/heads/main", &main, None).unwrap();
+        write_ref(&git_dir, "refs/heads/topic/a", &main, None).unwrap();
+        write_symbolic_ref(&git_dir, "HEAD", "refs/heads/main", None).unwrap();
+        assert!(write_ref(&git_dir, "refs/heads/bad..name", &main, None).is_err());
+
+        let refs = list_refs(&git_dir, "refs/").unwrap();
+        assert_eq!(
+            vec!["refs/heads/main", "refs/heads/topic/a", "refs/tags/v1.0"],
+            refs.iter().map(|r| r.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(main, refs[0].id);
+        assert_eq!(Some(main), refs[2].peeled);
+
+        assert!(delete_ref(&git_dir, "refs/heads/main").unwrap());
+        assert!(!delete_ref(&git_dir, "refs/heads/main").unwrap());
+        assert!(delete_ref(&git_dir, "refs/heads/topic/a").unwrap());
+        assert!(!git_dir.join("refs/heads/topic").exists());
+        assert_eq!(
+            vec![String::from("refs/tags/v1.0")],
+            list_refs(&git_dir, "")
+                .unwrap()
+                .into_iter()
+                .map(|r| r.name)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(None, resolve_ref(&git_dir, "HEAD").unwrap().1);
+
+        fs::remove_dir_all(git_dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_ref_format() {
+        for name in ["refs/heads/main", "refs/tags/v1.0", "heads/feature-1/x"] {
+            assert!(check_ref_format(name, false).is_ok(), "{}", name);
+        }
+
+        for name in [
+            "main",
+            "refs/heads/",
+            "refs//heads",
+            "refs/heads/.hidden",
+            "refs/heads/a..b",
+            "refs/heads/main.lock",
+            "refs/heads/main.",
+            "refs/heads/a b",
+            "refs/heads/a~1",
+            "refs/heads/a^",
+            "refs/heads/a:b",
+            "refs/heads/a?",
+            "refs/heads/a*",
+            "refs/heads/a[",
+            "refs/heads/a\\b",
+            "refs/heads/a@{1}",
+            "@",
+        ] {
+            assert!(check_ref_format(name, false).is_err(), "{}", name);
+        }
+
+        assert!(check_ref_format("main", true).is_ok());
+        assert!(validate_ref_name("HEAD").is_ok());
+        assert!(validate_ref_name("ORIG_HEAD").is_ok());
+        assert!(validate_ref_name("heads/main").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::{fs, io, path::Path};
+
+use super::lock::LockFile;
+use crate::{obj::id::ObjectId, Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedRef {
+    pub name: String,
+    pub id: ObjectId,
+    /// The non-tag object an annotated tag peels to, from the `^<id>` line following it.
+    pub peeled: Option<ObjectId>,
+}
+
+/// The content of `packed-refs`, keeping its `# pack-refs with:` header line.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PackedRefs {
+    header: Option<String>,
+    refs: Vec<PackedRef>,
+}
+
+impl PackedRefs {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut packed = Self::default();
+
+        for line in input.lines() {
+            let invalid = || Error::Generic(format!("unexpected line in packed-refs: {}", line));
+
+            if line.starts_with('#') {
+                if packed.header.is_none() && packed.refs.is_empty() {
+                    packed.header = Some(line.to_owned());
+                }
+            } else if let Some(peeled) = line.strip_prefix('^') {
+                let last = packed.refs.last_mut().ok_or_else(invalid)?;
+                last.peeled = Some(peeled.parse().map_err(|_| invalid())?);
+            } else if !line.is_empty() {
+                let (id, name) = line.split_once(' ').ok_or_else(invalid)?;
+
+                packed.refs.push(PackedRef {
+                    name: name.to_owned(),
+                    id: id.parse().map_err(|_| invalid())?,
+                    peeled: None,
+                });
+            }
+        }
+
+        Ok(packed)
+    }
+
+    /// Reads `<git_dir>/packed-refs`, which may be missing.
+    pub fn open(git_dir: &Path) -> Result<Self> {
+        match fs::read_to_string(git_dir.join("packed-refs")) {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn refs(&self) -> &[PackedRef] {
+        &self.refs
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PackedRef> {
+        self.refs.iter().find(|r| r.name == name)
+    }
+
+    /// Removes `name`, returning whether it was present.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.refs.len();
+        self.refs.retain(|r| r.name != name);
+
+        self.refs.len() != len
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = String::new();
+
+        if let Some(header) = &self.header {
+            result.push_str(header);
+            result.push('\n');
+        }
+
+        for packed_ref in &self.refs {
+            result.push_str(&format!("{} {}\n", packed_ref.id, packed_ref.name));
+
+            if let Some(peeled) = &packed_ref.peeled {
+                result.push_str(&format!("^{}\n", peeled));
+            }
+        }
+
+        result.into_bytes()
+    }
+
+    /// Replaces `packed-refs` under `<git_dir>` through a lock file.
+    pub fn save(&self, git_dir: &Path) -> Result<()> {
+        let mut lock = LockFile::acquire(git_dir.join("packed-refs"))?;
+        lock.write_all(&self.to_bytes())?;
+
+        lock.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packed_refs_round_trip() {
+        let input = "# pack-refs with: peeled fully-peeled sorted \n\
+            bd9dbf5aae1a3862dd1526723246b20206e5fc37 refs/heads/main\n\
+            45b983be36b73c0788dc9cbcb76cbb80fc7bb057 refs/tags/v1.0\n\
+            ^bd9dbf5aae1a3862dd1526723246b20206e5fc37\n";
+
+        let mut packed = PackedRefs::parse(input).unwrap();
+
+        assert_eq!(input.as_bytes(), packed.to_bytes().as_slice());
+        assert_eq!(
+            Some("bd9dbf5aae1a3862dd1526723246b20206e5fc37".parse().unwrap()),
+            packed.get("refs/tags/v1.0").unwrap().peeled
+        );
+
+        assert!(packed.remove("refs/tags/v1.0"));
+        assert!(!packed.remove("refs/tags/v1.0"));
+        assert_eq!(1, packed.refs().len());
+
+        assert!(PackedRefs::parse("^bd9dbf5aae1a3862dd1526723246b20206e5fc37\n").is_err());
+    }
+}
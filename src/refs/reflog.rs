@@ -0,0 +1,252 @@
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{collect_loose_refs, lock::LockFile, remove_empty_parents};
+use crate::{
+    config::{parse_bool, Config},
+    obj::{id::ObjectId, signature::Signature},
+    Error, Result,
+};
+
+/// One line of `logs/<ref>`: the ref moved from `old` to `new`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub old: ObjectId,
+    pub new: ObjectId,
+    pub committer: Signature,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    /// Parses `<old> <new> <name> <<email>> <time> <offset>\t<message>`.
+    pub fn parse(line: &str) -> Result<Self> {
+        let invalid = || Error::Generic(format!("invalid reflog entry: {}", line));
+
+        let (old, rest) = line.split_once(' ').ok_or_else(invalid)?;
+        let (new, rest) = rest.split_once(' ').ok_or_else(invalid)?;
+        let (committer, message) = rest.split_once('\t').unwrap_or((rest, ""));
+
+        Ok(Self {
+            old: old.parse()?,
+            new: new.parse()?,
+            committer: Signature::parse(committer)?,
+            message: message.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for ReflogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.old, self.new, self.committer)?;
+
+        match self.message.is_empty() {
+            true => Ok(()),
+            false => write!(f, "\t{}", self.message),
+        }
+    }
+}
+
+/// Which refs get a reflog created when they are updated, following `core.logAllRefUpdates`.
+/// Refs that already have a reflog are always logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRefUpdates {
+    Never,
+    /// `HEAD` and refs under `refs/heads/`, `refs/remotes/` and `refs/notes/`.
+    Branches,
+    Always,
+}
+
+impl LogRefUpdates {
+    /// Reads `core.logAllRefUpdates`, which defaults to logging branches outside bare repositories.
+    pub fn from_config(config: &Config, bare: bool) -> Result<Self> {
+        match config.get("core.logAllRefUpdates") {
+            None if bare => Ok(Self::Never),
+            None => Ok(Self::Branches),
+            Some(value) if value.eq_ignore_ascii_case("always") => Ok(Self::Always),
+            Some(value) => match parse_bool(value) {
+                Some(true) => Ok(Self::Branches),
+                Some(false) => Ok(Self::Never),
+                None => Err(Error::Config(format!(
+                    "invalid value for core.logAllRefUpdates: {}",
+                    value
+                ))),
+            },
+        }
+    }
+
+    pub fn creates(&self, name: &str) -> bool {
+        match self {
+            Self::Never => false,
+            Self::Branches => {
+                name == "HEAD"
+                    || ["refs/heads/", "refs/remotes/", "refs/notes/"]
+                        .iter()
+                        .any(|prefix| name.starts_with(prefix))
+            }
+            Self::Always => true,
+        }
+    }
+}
+
+/// Who moved a ref and why, appended to the reflog of every ref a transaction changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogMessage {
+    pub committer: Signature,
+    pub message: String,
+    pub create: LogRefUpdates,
+}
+
+impl ReflogMessage {
+    /// Collapses the whitespace in `message` to single spaces, as git does, so that it fits on
+    /// one line.
+    pub fn new(committer: Signature, message: &str, create: LogRefUpdates) -> Self {
+        Self {
+            committer,
+            message: message.split_whitespace().collect::<Vec<_>>().join(" "),
+            create,
+        }
+    }
+}
+
+fn log_path(git_dir: &Path, name: &str) -> PathBuf {
+    git_dir.join("logs").join(name)
+}
+
+pub fn reflog_exists(git_dir: &Path, name: &str) -> bool {
+    log_path(git_dir, name).is_file()
+}
+
+/// Reads the reflog of `name`, oldest entry first. A missing log is empty.
+pub fn read_reflog(git_dir: &Path, name: &str) -> Result<Vec<ReflogEntry>> {
+    let content = match fs::read_to_string(log_path(git_dir, name)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(ReflogEntry::parse)
+        .collect()
+}
+
+/// Records that `name` moved from `old` to `new`, unless it has no reflog and `reflog.create`
+/// does not cover it.
+pub fn append_reflog(
+    git_dir: &Path,
+    name: &str,
+    old: ObjectId,
+    new: ObjectId,
+    reflog: &ReflogMessage,
+) -> Result<()> {
+    let path = log_path(git_dir, name);
+
+    if !path.is_file() && !reflog.create.creates(name) {
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let entry = ReflogEntry {
+        old,
+        new,
+        committer: reflog.committer.clone(),
+        message: reflog.message.clone(),
+    };
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+
+    Ok(writeln!(file, "{}", entry)?)
+}
+
+/// Replaces the reflog of `name` with `entries`, oldest first.
+pub fn write_reflog(git_dir: &Path, name: &str, entries: &[ReflogEntry]) -> Result<()> {
+    let mut lock = LockFile::acquire(log_path(git_dir, name))?;
+
+    for entry in entries {
+        lock.write_all(format!("{}\n", entry).as_bytes())?;
+    }
+
+    lock.commit()
+}
+
+pub fn delete_reflog(git_dir: &Path, name: &str) -> Result<()> {
+    let path = log_path(git_dir, name);
+
+    match fs::remove_file(&path) {
+        Ok(()) => {
+            remove_empty_parents(&git_dir.join("logs").join("refs"), &path);
+
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Lists the refs that have a reflog, sorted by name.
+pub fn list_reflogs(git_dir: &Path) -> Result<Vec<String>> {
+    let logs_dir = git_dir.join("logs");
+    let mut names = Vec::new();
+
+    if reflog_exists(git_dir, "HEAD") {
+        names.push(String::from("HEAD"));
+    }
+
+    collect_loose_refs(&logs_dir, &logs_dir.join("refs"), &mut names)?;
+    names.sort();
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn test_append_and_rewrite_reflog() {
+        let git_dir = env::temp_dir().join(format!("reflog-test-{}", process::id()));
+        fs::create_dir_all(&git_dir).unwrap();
+
+        let a: ObjectId = "bd9dbf5aae1a3862dd1526723246b20206e5fc37".parse().unwrap();
+        let b: ObjectId = "45b983be36b73c0788dc9cbcb76cbb80fc7bb057".parse().unwrap();
+        let null = ObjectId::null(a.format());
+        let committer = Signature::new("A U Thor", "author@example.com", 1700000000, 60);
+        let reflog = ReflogMessage::new(committer, "update:\n  moved", LogRefUpdates::Branches);
+
+        append_reflog(&git_dir, "refs/heads/main", null, a, &reflog).unwrap();
+        append_reflog(&git_dir, "refs/heads/main", a, b, &reflog).unwrap();
+        append_reflog(&git_dir, "refs/tags/v1.0", null, a, &reflog).unwrap();
+
+        assert_eq!(
+            format!(
+                "{null} {a} A U Thor <author@example.com> 1700000000 +0100\tupdate: moved\n\
+                {a} {b} A U Thor <author@example.com> 1700000000 +0100\tupdate: moved\n"
+            ),
+            fs::read_to_string(git_dir.join("logs/refs/heads/main")).unwrap()
+        );
+        assert_eq!(vec!["refs/heads/main"], list_reflogs(&git_dir).unwrap());
+
+        let mut entries = read_reflog(&git_dir, "refs/heads/main").unwrap();
+        entries.remove(0);
+        entries[0].message.clear();
+        write_reflog(&git_dir, "refs/heads/main", &entries).unwrap();
+        assert_eq!(entries, read_reflog(&git_dir, "refs/heads/main").unwrap());
+
+        delete_reflog(&git_dir, "refs/heads/main").unwrap();
+        assert!(!git_dir.join("logs/refs/heads").exists());
+
+        fs::remove_dir_all(git_dir).unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    lock::LockFile,
+    packed::PackedRefs,
+    read_ref,
+    reflog::{append_reflog, delete_reflog, ReflogMessage},
+    resolve_ref, validate_ref_name, RefValue,
+};
+use crate::{obj::id::ObjectId, Error, Result};
+
+/// A single change queued in a [`Transaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefUpdate {
+    pub name: String,
+    /// The value to store, the null id to delete the ref, or `None` to only verify it.
+    pub new: Option<ObjectId>,
+    /// The value the ref must have before the update, the null id meaning it must not exist.
+    pub old: Option<ObjectId>,
+}
+
+/// A set of ref updates applied all-or-nothing. Every ref is locked through `<ref>.lock` and
+/// checked against its expected old value before any of them is changed.
+pub struct Transaction<'a> {
+    git_dir: &'a Path,
+    updates: Vec<RefUpdate>,
+    reflog: Option<ReflogMessage>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new(git_dir: &'a Path) -> Self {
+        Self {
+            git_dir,
+            updates: Vec::new(),
+            reflog: None,
+        }
+    }
+
+    /// Records every ref moved by the transaction in its reflog. Without a message no reflog is
+    /// written.
+    pub fn reflog(&mut self, reflog: ReflogMessage) -> &mut Self {
+        self.reflog = Some(reflog);
+
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    pub fn push(&mut self, update: RefUpdate) -> Result<&mut Self> {
+        validate_ref_name(&update.name)?;
+
+        if self.updates.iter().any(|u| u.name == update.name) {
+            return Err(Error::Generic(format!(
+                "multiple updates for ref '{}' not allowed",
+                update.name
+            )));
+        }
+
+        self.updates.push(update);
+
+        Ok(self)
+    }
+
+    /// Points `name` at `new`, optionally checking that it currently has the value `old`.
+    pub fn update(
+        &mut self,
+        name: impl ToString,
+        new: ObjectId,
+        old: Option<ObjectId>,
+    ) -> Result<&mut Self> {
+        self.push(RefUpdate {
+            name: name.to_string(),
+            new: Some(new),
+            old,
+        })
+    }
+
+    /// Creates `name`, which must not exist yet.
+    pub fn create(&mut self, name: impl ToString, new: ObjectId) -> Result<&mut Self> {
+        let old = ObjectId::null(new.format());
+
+        self.update(name, new, Some(old))
+    }
+
+    pub fn delete(&mut self, name: impl ToString, old: Option<ObjectId>) -> Result<&mut Self> {
+        let name = name.to_string();
+        let null = ObjectId::null(old.map_or_else(Default::default, |old| old.format()));
+
+        self.push(RefUpdate {
+            name,
+            new: Some(null),
+            old,
+        })
+    }
+
+    /// Checks that `name` has the value `old` without changing it.
+    pub fn verify(&mut self, name: impl ToString, old: ObjectId) -> Result<&mut Self> {
+        self.push(RefUpdate {
+            name: name.to_string(),
+            new: None,
+            old: Some(old),
+        })
+    }
+
+    /// Locks every ref and verifies the old values. Dropping the result releases the locks.
+    pub fn prepare(self) -> Result<PreparedTransaction> {
+        let mut updates = self.updates;
+        updates.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut locks = Vec::with_capacity(updates.len());
+
+        for update in &updates {
+            let mut lock = LockFile::acquire(self.git_dir.join(&update.name))?;
+
+            let current = match read_ref(self.git_dir, &update.name)? {
+                Some(RefValue::Direct(id)) => Some(id),
+                Some(RefValue::Symbolic(_)) => resolve_ref(self.git_dir, &update.name)?.1,
+                None => None,
+            };
+
+            if let Some(old) = update.old {
+                verify_current(&update.name, current, old)?;
+            }
+
+            if let Some(new) = update.new.filter(|new| !new.is_null()) {
+                lock.write_all(format!("{}\n", new).as_bytes())?;
+            }
+
+            locks.push((current, lock));
+        }
+
+        let deleted = updates
+            .iter()
+            .filter(|u| u.new.is_some_and(|new| new.is_null()))
+            .map(|u| u.name.as_str())
+            .collect::<HashSet<_>>();
+
+        // Lock before reading, so that refs packed or deleted concurrently are not lost.
+        let packed_lock = match deleted.is_empty() {
+            true => None,
+            false => {
+                let mut lock = LockFile::acquire(self.git_dir.join("packed-refs"))?;
+                let mut packed = PackedRefs::open(self.git_dir)?;
+                let mut packed_changed = false;
+
+                for name in &deleted {
+                    packed_changed |= packed.remove(name);
+                }
+
+                match packed_changed {
+                    true => {
+                        lock.write_all(&packed.to_bytes())?;
+                        Some(lock)
+                    }
+                    false => None,
+                }
+            }
+        };
+
+        // Moving the branch `HEAD` points at also moves `HEAD`, so its reflog records it too.
+        let head = match (&self.reflog, read_ref(self.git_dir, "HEAD")?) {
+            (Some(_), Some(RefValue::Symbolic(target)))
+                if !updates.iter().any(|u| u.name == "HEAD") =>
+            {
+                Some(target)
+            }
+            _ => None,
+        };
+
+        Ok(PreparedTransaction {
+            git_dir: self.git_dir.to_owned(),
+            updates: updates
+                .into_iter()
+                .zip(locks)
+                .map(|(update, (current, lock))| LockedUpdate {
+                    update,
+                    current,
+                    lock,
+                })
+                .collect(),
+            packed_lock,
+            reflog: self.reflog,
+            head,
+        })
+    }
+
+    pub fn commit(self) -> Result<()> {
+        self.prepare()?.commit()
+    }
+}
+
+struct LockedUpdate {
+    update: RefUpdate,
+    /// The value of the ref when it was locked.
+    current: Option<ObjectId>,
+    lock: LockFile,
+}
+
+/// A transaction whose refs are all locked and verified, ready to be committed.
+pub struct PreparedTransaction {
+    git_dir: PathBuf,
+    updates: Vec<LockedUpdate>,
+    packed_lock: Option<LockFile>,
+    reflog: Option<ReflogMessage>,
+    /// The ref `HEAD` points at, when it is not part of the transaction itself.
+    head: Option<String>,
+}
+
+impl PreparedTransaction {
+    /// Applies the updates. Refs only being verified are released unchanged.
+    pub fn commit(self) -> Result<()> {
+        if let Some(packed_lock) = self.packed_lock {
+            packed_lock.commit()?;
+        }
+
+        for LockedUpdate {
+            update,
+            current,
+            lock,
+        } in self.updates
+        {
+            match update.new {
+                Some(new) if new.is_null() => {
+                    let path = lock.path().to_owned();
+                    lock.commit_delete()?;
+                    super::remove_empty_parents(&self.git_dir.join("refs"), &path);
+                    delete_reflog(&self.git_dir, &update.name)?;
+                }
+                Some(new) => {
+                    lock.commit()?;
+
+                    let reflog = match &self.reflog {
+                        Some(reflog) if current != Some(new) => reflog,
+                        _ => continue,
+                    };
+                    let old = current.unwrap_or_else(|| ObjectId::null(new.format()));
+
+                    append_reflog(&self.git_dir, &update.name, old, new, reflog)?;
+
+                    if self.head.as_deref() == Some(update.name.as_str()) {
+                        append_reflog(&self.git_dir, "HEAD", old, new, reflog)?;
+                    }
+                }
+                None => drop(lock),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that a ref currently has the `expected` value, the null id meaning it must not exist.
+pub fn verify_current(name: &str, current: Option<ObjectId>, expected: ObjectId) -> Result<()> {
+    match current {
+        Some(current) if expected.is_null() => Err(Error::Generic(format!(
+            "cannot lock ref '{}': reference already exists at {}",
+            name, current
+        ))),
+        Some(current) if current != expected => Err(Error::Generic(format!(
+            "cannot lock ref '{}': is at {} but expected {}",
+            name, current, expected
+        ))),
+        None if !expected.is_null() => Err(Error::Generic(format!(
+            "cannot lock ref '{}': unable to resolve reference",
+            name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{
+        obj::signature::Signature,
+        refs::{
+            list_refs,
+            reflog::{read_reflog, reflog_exists, LogRefUpdates},
+        },
+    };
+
+    #[test]
+    fn test_transaction() {
+        let git_dir = env::temp_dir().join(format!("transaction-test-{}", process::id()));
+        fs::create_dir_all(git_dir.join("refs")).unwrap();
+
+        let a: ObjectId = "bd9dbf5aae1a3862dd1526723246b20206e5fc37".parse().unwrap();
+        let b: ObjectId = "45b983be36b73c0788dc9cbcb76cbb80fc7bb057".parse().unwrap();
+        let names = || {
+            list_refs(&git_dir, "")
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.name, r.id))
+                .collect::<Vec<_>>()
+        };
+
+        let mut transaction = Transaction::new(&git_dir);
+        transaction.create("refs/heads/main", a).unwrap();
+        transaction.create("refs/heads/topic", b).unwrap();
+        assert!(transaction.create("refs/heads/main", b).is_err());
+        transaction.commit().unwrap();
+
+        assert_eq!(
+            vec![
+                (String::from("refs/heads/main"), a),
+                (String::from("refs/heads/topic"), b)
+            ],
+            names()
+        );
+
+        // A failed check leaves every ref untouched and releases all locks.
+        let mut transaction = Transaction::new(&git_dir);
+        transaction.update("refs/heads/main", b, Some(a)).unwrap();
+        transaction.delete("refs/heads/topic", Some(a)).unwrap();
+        assert!(transaction.commit().is_err());
+        assert_eq!(a, names()[0].1);
+        assert!(!git_dir.join("refs/heads/main.lock").exists());
+
+        // A ref that is already locked fails the whole transaction.
+        fs::write(git_dir.join("refs/heads/topic.lock"), "").unwrap();
+        let mut transaction = Transaction::new(&git_dir);
+        transaction.update("refs/heads/main", b, Some(a)).unwrap();
+        transaction.delete("refs/heads/topic", None).unwrap();
+        assert!(transaction.commit().is_err());
+        assert_eq!(a, names()[0].1);
+        fs::remove_file(git_dir.join("refs/heads/topic.lock")).unwrap();
+
+        let mut transaction = Transaction::new(&git_dir);
+        transaction.update("refs/heads/main", b, Some(a)).unwrap();
+        transaction.delete("refs/heads/topic", Some(b)).unwrap();
+        transaction
+            .verify("refs/heads/other", ObjectId::null(a.format()))
+            .unwrap();
+        transaction.commit().unwrap();
+
+        assert_eq!(vec![(String::from("refs/heads/main"), b)], names());
+
+        // Moving the branch `HEAD` points at logs both, deleting a ref drops its log.
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let committer = Signature::new("A U Thor", "author@example.com", 1700000000, 60);
+        let reflog = ReflogMessage::new(committer, "reset", LogRefUpdates::Branches);
+
+        let mut transaction = Transaction::new(&git_dir);
+        transaction.update("refs/heads/main", a, Some(b)).unwrap();
+        transaction.create("refs/heads/topic", a).unwrap();
+        transaction.reflog(reflog);
+        transaction.commit().unwrap();
+
+        let head_log = read_reflog(&git_dir, "HEAD").unwrap();
+        assert_eq!(1, head_log.len());
+        assert_eq!((b, a), (head_log[0].old, head_log[0].new));
+        assert_eq!(head_log, read_reflog(&git_dir, "refs/heads/main").unwrap());
+        assert!(reflog_exists(&git_dir, "refs/heads/topic"));
+
+        let mut transaction = Transaction::new(&git_dir);
+        transaction.delete("refs/heads/topic", Some(a)).unwrap();
+        transaction.commit().unwrap();
+        assert!(!reflog_exists(&git_dir, "refs/heads/topic"));
+
+        // Deletions hold `packed-refs.lock` while reading the packed refs they rewrite.
+        fs::write(git_dir.join("packed-refs"), format!("{} refs/tags/v1\n", a)).unwrap();
+        fs::write(git_dir.join("packed-refs.lock"), "").unwrap();
+        let mut transaction = Transaction::new(&git_dir);
+        transaction.delete("refs/tags/v1", Some(a)).unwrap();
+        assert!(transaction.commit().is_err());
+        fs::remove_file(git_dir.join("packed-refs.lock")).unwrap();
+
+        let mut transaction = Transaction::new(&git_dir);
+        transaction.delete("refs/tags/v1", Some(a)).unwrap();
+        transaction.commit().unwrap();
+        assert_eq!("", fs::read_to_string(git_dir.join("packed-refs")).unwrap());
+
+        fs::remove_dir_all(git_dir).unwrap();
+    }
+}
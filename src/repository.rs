@@ -0,0 +1,291 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config,
+    index::Index,
+    obj::{
+        store::{LooseObjectDatabase, ObjectDatabase},
+        user::{Role, User},
+    },
+    refs::{
+        reflog::{LogRefUpdates, ReflogMessage},
+        write_symbolic_ref,
+    },
+    sha::ObjectFormat,
+    Error, Result,
+};
+
+/// Abbreviation length for object ids when `core.abbrev` is not set.
+const DEFAULT_ABBREV_LEN: usize = 7;
+
+/// A git directory together with its work tree, config and object database.
+pub struct Repository {
+    git_dir: PathBuf,
+    work_tree: Option<PathBuf>,
+    config: Config,
+    format: ObjectFormat,
+    odb: Box<dyn ObjectDatabase>,
+}
+
+impl Repository {
+    pub fn new(
+        git_dir: impl Into<PathBuf>,
+        work_tree: Option<PathBuf>,
+        config: Config,
+        odb: Box<dyn ObjectDatabase>,
+    ) -> Self {
+        Self {
+            git_dir: git_dir.into(),
+            work_tree,
+            config,
+            format: odb.format(),
+            odb,
+        }
+    }
+
+    /// Opens the repository at `git_dir` with loose objects stored in `<git_dir>/objects`.
+    pub fn open(git_dir: impl Into<PathBuf>, work_tree: Option<PathBuf>) -> Result<Self> {
+        let git_dir = git_dir.into();
+        let config = Config::load(Some(&git_dir))?;
+        let odb = LooseObjectDatabase::open(git_dir.join("objects"), &config)?;
+
+        Ok(Self::new(git_dir, work_tree, config, Box::new(odb)))
+    }
+
+    /// Finds the repository containing the current directory.
+    pub fn discover() -> Result<Self> {
+        Self::discover_from(&env::current_dir()?)
+    }
+
+    /// Finds the repository containing `cwd` the way git does: `GIT_DIR` if set, otherwise the
+    /// first `.git` directory or gitfile, or bare repository, found walking up from `cwd` without
+    /// entering any of `GIT_CEILING_DIRECTORIES`. `GIT_WORK_TREE`, `core.worktree`, `core.bare`
+    /// and `GIT_OBJECT_DIRECTORY` are honored once the git directory is known.
+    pub fn discover_from(cwd: &Path) -> Result<Self> {
+        let (git_dir, work_tree) = match env::var_os("GIT_DIR") {
+            Some(git_dir) => (cwd.join(git_dir), Some(cwd.to_owned())),
+            None => find_git_dir(cwd)?,
+        };
+
+        let config = Config::load(Some(&git_dir))?;
+
+        let work_tree = match env::var_os("GIT_WORK_TREE") {
+            Some(work_tree) => Some(cwd.join(work_tree)),
+            None => match config.get_path("core.worktree")? {
+                Some(work_tree) => Some(git_dir.join(work_tree)),
+                None if config.get_bool("core.bare")? == Some(true) => None,
+                None => work_tree,
+            },
+        };
+
+        let objects_dir = match env::var_os("GIT_OBJECT_DIRECTORY") {
+            Some(objects_dir) => cwd.join(objects_dir),
+            None => git_dir.join("objects"),
+        };
+
+        let odb = LooseObjectDatabase::open(objects_dir, &config)?;
+
+        Ok(Self::new(git_dir, work_tree, config, Box::new(odb)))
+    }
+
+    /// Creates an empty repository with its git directory at `<work_tree>/.git`.
+    pub fn init(work_tree: &Path, format: ObjectFormat) -> Result<Self> {
+        let git_dir = work_tree.join(".git");
+
+        fs::create_dir_all(work_tree)?;
+        fs::create_dir(&git_dir)?;
+        fs::create_dir(git_dir.join("objects"))?;
+        fs::create_dir(git_dir.join("refs"))?;
+        write_symbolic_ref(&git_dir, "HEAD", "refs/heads/master", None)?;
+
+        let mut config = String::from("[core]\n");
+
+        match format {
+            ObjectFormat::Sha1 => config.push_str("\trepositoryformatversion = 0\n"),
+            ObjectFormat::Sha256 => config.push_str("\trepositoryformatversion = 1\n"),
+        }
+        config.push_str(
+            "\tfilemode = true\n\
+             \tbare = false\n\
+             \tlogallrefupdates = true\n",
+        );
+
+        if format != ObjectFormat::Sha1 {
+            config.push_str(&format!("[extensions]\n\tobjectformat = {}\n", format));
+        }
+
+        fs::write(git_dir.join("config"), config)?;
+
+        Self::open(git_dir, Some(work_tree.to_owned()))
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    /// Returns `None` for a bare repository.
+    pub fn work_tree(&self) -> Option<&Path> {
+        self.work_tree.as_deref()
+    }
+
+    /// Like [`Repository::work_tree`], but fails for a bare repository.
+    pub fn require_work_tree(&self) -> Result<&Path> {
+        self.work_tree().ok_or_else(|| {
+            Error::Generic(String::from("this operation must be run in a work tree"))
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn object_format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    pub fn odb(&self) -> &dyn ObjectDatabase {
+        self.odb.as_ref()
+    }
+
+    /// Returns the path of the index file, `$GIT_INDEX_FILE` or `index` in the git dir.
+    pub fn index_path(&self) -> PathBuf {
+        match env::var_os("GIT_INDEX_FILE").filter(|path| !path.is_empty()) {
+            Some(path) => PathBuf::from(path),
+            None => self.git_dir.join("index"),
+        }
+    }
+
+    /// Reads the index, which is empty when the file does not exist yet.
+    pub fn index(&self) -> Result<Index> {
+        Index::open(&self.index_path(), self.format)
+    }
+
+    /// Returns the minimum length of abbreviated object ids, from `core.abbrev`.
+    pub fn abbrev_len(&self) -> Result<usize> {
+        Ok(match self.config.get_int("core.abbrev")? {
+            Some(len) => len.try_into().unwrap_or(DEFAULT_ABBREV_LEN),
+            None => DEFAULT_ABBREV_LEN,
+        })
+    }
+
+    /// Stamps `message` with the committer identity for the reflogs of the refs a command moves.
+    /// Like git, falls back to the default identity when none is configured.
+    pub fn reflog_message(&self, message: &str) -> Result<ReflogMessage> {
+        let committer = User::signature_or_default(Role::Committer, &self.config)?;
+        let create = LogRefUpdates::from_config(&self.config, self.work_tree.is_none())?;
+
+        Ok(ReflogMessage::new(committer, message, create))
+    }
+}
+
+/// Walks up from `cwd` looking for a git directory, returning it with its default work tree.
+fn find_git_dir(cwd: &Path) -> Result<(PathBuf, Option<PathBuf>)> {
+    let ceilings = ceiling_dirs();
+    let mut dir = Some(cwd);
+
+    while let Some(current) = dir {
+        let dot_git = current.join(".git");
+
+        if dot_git.is_file() {
+            return Ok((read_git_file(&dot_git)?, Some(current.to_owned())));
+        }
+        if is_git_dir(&dot_git) {
+            return Ok((dot_git, Some(current.to_owned())));
+        }
+        if is_git_dir(current) {
+            return Ok((current.to_owned(), None));
+        }
+
+        dir = current
+            .parent()
+            .filter(|parent| !ceilings.iter().any(|ceiling| ceiling == parent));
+    }
+
+    Err(Error::NotARepository(cwd.to_owned()))
+}
+
+/// Parses the `GIT_CEILING_DIRECTORIES` list, ignoring empty and relative entries.
+fn ceiling_dirs() -> Vec<PathBuf> {
+    let ceilings = match env::var_os("GIT_CEILING_DIRECTORIES") {
+        Some(ceilings) => ceilings,
+        None => return Vec::new(),
+    };
+
+    env::split_paths(&ceilings)
+        .filter(|path| path.is_absolute())
+        .map(|path| path.canonicalize().unwrap_or(path))
+        .collect()
+}
+
+/// Returns the git directory of a repository nested at `path` in a work tree, such as a
+/// submodule, found through its `.git` directory or gitfile.
+pub fn nested_git_dir(path: &Path) -> Option<PathBuf> {
+    let dot_git = path.join(".git");
+
+    if dot_git.is_file() {
+        return read_git_file(&dot_git).ok();
+    }
+
+    is_git_dir(&dot_git).then_some(dot_git)
+}
+
+fn is_git_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+/// Resolves a `.git` file containing `gitdir: <path>`, as used by worktrees and submodules.
+fn read_git_file(path: &Path) -> Result<PathBuf> {
+    let content = fs::read_to_string(path)?;
+
+    let git_dir = content
+        .strip_prefix("gitdir: ")
+        .map(str::trim_end)
+        .ok_or_else(|| Error::Generic(format!("invalid gitfile format: {}", path.display())))?;
+
+    let git_dir = match path.parent() {
+        Some(parent) => parent.join(git_dir),
+        None => PathBuf::from(git_dir),
+    };
+
+    if !is_git_dir(&git_dir) {
+        return Err(Error::Generic(format!(
+            "not a git repository: {}",
+            git_dir.display()
+        )));
+    }
+
+    Ok(git_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_git_dir() {
+        let root = env::temp_dir().join(format!("repository-test-{}", std::process::id()));
+        let nested = root.join("work/a/b");
+        fs::create_dir_all(&nested).unwrap();
+
+        Repository::init(&root.join("work"), ObjectFormat::Sha1).unwrap();
+
+        let (git_dir, work_tree) = find_git_dir(&nested).unwrap();
+        assert_eq!(root.join("work/.git"), git_dir);
+        assert_eq!(Some(root.join("work")), work_tree);
+
+        let (git_dir, work_tree) = find_git_dir(&root.join("work/.git")).unwrap();
+        assert_eq!(root.join("work/.git"), git_dir);
+        assert_eq!(None, work_tree);
+
+        fs::create_dir_all(root.join("linked")).unwrap();
+        fs::write(root.join("linked/.git"), "gitdir: ../work/.git\n").unwrap();
+        let (git_dir, work_tree) = find_git_dir(&root.join("linked")).unwrap();
+        assert_eq!(root.join("linked/../work/.git"), git_dir);
+        assert_eq!(Some(root.join("linked")), work_tree);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
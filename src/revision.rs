@@ -0,0 +1,419 @@
+use crate::{
+    obj::{
+        id::ObjectId,
+        signature::{approxidate, current_time},
+        Object,
+    },
+    refs::{dwim_ref, reflog::read_reflog, resolve_ref},
+    repository::Repository,
+    Error, Result,
+};
+
+/// Resolves a revision expression to an object id.
+///
+/// Supported forms are ref names and full or abbreviated ids as a base, `@` for `HEAD`,
+/// `<ref>@{<n>}`, `<ref>@{<date>}` and `@{-<n>}` reflog lookups, followed by any number of
+/// `~<n>`, `^<n>` and `^{<type>}` suffixes, and finally an optional `:<path>` into the
+/// resulting tree.
+pub fn resolve(repo: &Repository, spec: &str) -> Result<ObjectId> {
+    let (rev, path) = match find_outside_braces(spec, |c| c == ':') {
+        Some(colon) => (&spec[..colon], Some(&spec[colon + 1..])),
+        None => (spec, None),
+    };
+
+    if rev.is_empty() {
+        return Err(Error::UnknownRevision(spec.to_owned()));
+    }
+
+    let id = resolve_rev(repo, rev, spec)?;
+
+    match path {
+        Some(path) => lookup_path(repo, peel(repo, id, "tree")?, path, rev),
+        None => Ok(id),
+    }
+}
+
+/// Dereferences tags, and commits to their tree, until an object of `kind` is reached.
+pub fn peel(repo: &Repository, mut id: ObjectId, kind: &str) -> Result<ObjectId> {
+    loop {
+        let object = repo.odb().read(&id)?;
+
+        if object.kind() == kind {
+            return Ok(id);
+        }
+
+        id = match object {
+            Object::Tag(tag) => *tag.object_sha(),
+            Object::Commit(commit) if kind == "tree" => *commit.tree_sha(),
+            object => {
+                return Err(Error::Generic(format!(
+                    "{}: expected {} type, but the object dereferences to {} type",
+                    id,
+                    kind,
+                    object.kind()
+                )))
+            }
+        };
+    }
+}
+
+/// Finds the first char matching `pred` that is not inside `{...}`.
+fn find_outside_braces(input: &str, pred: impl Fn(char) -> bool) -> Option<usize> {
+    let mut depth = 0usize;
+
+    for (i, c) in input.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            c if depth == 0 && pred(c) => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn resolve_rev(repo: &Repository, rev: &str, spec: &str) -> Result<ObjectId> {
+    let base_end = find_outside_braces(rev, |c| c == '~' || c == '^').unwrap_or(rev.len());
+    let mut id = resolve_base(repo, &rev[..base_end], spec)?;
+    let mut rest = &rev[base_end..];
+
+    let invalid = || Error::UnknownRevision(spec.to_owned());
+
+    while let Some(op) = rest.chars().next() {
+        if op != '~' && op != '^' {
+            return Err(invalid());
+        }
+        rest = &rest[1..];
+
+        if op == '^' && rest.starts_with('{') {
+            let end = rest.find('}').ok_or_else(invalid)?;
+            let kind = &rest[1..end];
+            rest = &rest[end + 1..];
+
+            id = match kind {
+                "" => peel_tags(repo, id)?,
+                "object" => repo.odb().read_header(&id).map(|_| id)?,
+                "commit" | "tree" | "blob" | "tag" => peel(repo, id, kind)?,
+                _ => return Err(invalid()),
+            };
+
+            continue;
+        }
+
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let number = match digits {
+            0 => 1,
+            _ => rest[..digits].parse::<usize>().map_err(|_| invalid())?,
+        };
+        rest = &rest[digits..];
+
+        match (op, number) {
+            ('~', n) => {
+                for _ in 0..n {
+                    id = nth_parent(repo, id, 1).ok_or_else(invalid)??;
+                }
+            }
+            ('^', 0) => id = peel(repo, id, "commit")?,
+            (_, n) => id = nth_parent(repo, id, n).ok_or_else(invalid)??,
+        }
+    }
+
+    Ok(id)
+}
+
+/// Dereferences tags until a non-tag object is reached.
+pub fn peel_tags(repo: &Repository, mut id: ObjectId) -> Result<ObjectId> {
+    while let Object::Tag(tag) = repo.odb().read(&id)? {
+        id = *tag.object_sha();
+    }
+
+    Ok(id)
+}
+
+/// Returns the `n`th parent of the commit that `id` peels to, or `None` if it has fewer parents.
+fn nth_parent(repo: &Repository, id: ObjectId, n: usize) -> Option<Result<ObjectId>> {
+    let commit = match peel(repo, id, "commit").and_then(|id| repo.odb().read(&id)) {
+        Ok(commit) => commit,
+        Err(e) => return Some(Err(e)),
+    };
+
+    commit
+        .as_commit()?
+        .parent_shas()
+        .get(n.checked_sub(1)?)
+        .copied()
+        .map(Ok)
+}
+
+fn resolve_base(repo: &Repository, base: &str, spec: &str) -> Result<ObjectId> {
+    let unknown = || Error::UnknownRevision(spec.to_owned());
+
+    if let Some(at) = base.find("@{") {
+        let name = &base[..at];
+        let selector = base[at + 2..].strip_suffix('}').ok_or_else(unknown)?;
+
+        if let Some(n) = selector.strip_prefix('-') {
+            if !name.is_empty() {
+                return Err(unknown());
+            }
+
+            let n = n.parse().map_err(|_| unknown())?;
+            let branch = previous_checkout(repo, n)?.ok_or_else(unknown)?;
+
+            return resolve_base(repo, &branch, spec);
+        }
+
+        let ref_name = match name {
+            "" | "@" => resolve_ref(repo.git_dir(), "HEAD")?.0,
+            name => dwim_ref(repo.git_dir(), name)?.ok_or_else(unknown)?.0,
+        };
+
+        // Like git, numbers too large to be an entry count are read as timestamps.
+        match selector.parse::<usize>() {
+            Ok(n) if n < 100_000_000 => return reflog_entry(repo, &ref_name, n),
+            Ok(n) => return reflog_at_time(repo, &ref_name, n as i64),
+            Err(_) => {}
+        }
+
+        let time = approxidate(selector, current_time()).ok_or_else(|| {
+            Error::Generic(format!("unsupported reflog selector: @{{{}}}", selector))
+        })?;
+
+        return reflog_at_time(repo, &ref_name, time);
+    }
+
+    let base = if base == "@" { "HEAD" } else { base };
+
+    if base.len() == repo.object_format().hex_len() {
+        if let Ok(id) = base.parse() {
+            return Ok(id);
+        }
+    }
+
+    if let Some((_, id)) = dwim_ref(repo.git_dir(), base)? {
+        return Ok(id);
+    }
+
+    match repo.odb().resolve_prefix(base) {
+        Ok(id) => Ok(id),
+        Err(Error::InvalidObjectId(_) | Error::ObjectNotFound(_)) => Err(unknown()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the value `ref_name` had `n` updates ago, according to its reflog.
+fn reflog_entry(repo: &Repository, ref_name: &str, n: usize) -> Result<ObjectId> {
+    let entries = read_reflog(repo.git_dir(), ref_name)?;
+
+    let id = match entries.len().checked_sub(n + 1) {
+        Some(i) => Some(entries[i].new),
+        // Going one past the oldest entry yields the value the ref had before it.
+        None if n == entries.len() => entries.first().map(|e| e.old),
+        None => None,
+    };
+
+    id.filter(|id| !id.is_null()).ok_or_else(|| {
+        Error::Generic(format!(
+            "log for '{}' only has {} entries",
+            ref_name,
+            entries.len()
+        ))
+    })
+}
+
+/// Returns the value `ref_name` had at `time`, according to its reflog. Times before the oldest
+/// entry give the value the ref had when the log started.
+fn reflog_at_time(repo: &Repository, ref_name: &str, time: i64) -> Result<ObjectId> {
+    let entries = read_reflog(repo.git_dir(), ref_name)?;
+    let oldest = entries
+        .first()
+        .ok_or_else(|| Error::Generic(format!("log for '{}' is empty", ref_name)))?;
+
+    Ok(
+        match entries.iter().rev().find(|e| e.committer.time <= time) {
+            Some(entry) => entry.new,
+            None if oldest.old.is_null() => oldest.new,
+            None => oldest.old,
+        },
+    )
+}
+
+/// Returns the branch or commit that was checked out `n` checkouts ago.
+fn previous_checkout(repo: &Repository, n: usize) -> Result<Option<String>> {
+    if n == 0 {
+        return Ok(None);
+    }
+
+    let previous = read_reflog(repo.git_dir(), "HEAD")?
+        .into_iter()
+        .rev()
+        .filter_map(|entry| {
+            let (from, _) = entry
+                .message
+                .strip_prefix("checkout: moving from ")?
+                .split_once(" to ")?;
+
+            Some(from.to_owned())
+        })
+        .nth(n - 1);
+
+    Ok(previous)
+}
+
+fn lookup_path(repo: &Repository, tree: ObjectId, path: &str, rev: &str) -> Result<ObjectId> {
+    let mut id = tree;
+
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let object = repo.odb().read(&id)?;
+
+        id = object
+            .as_tree()
+            .and_then(|tree| tree.entry(name))
+            .map(|entry| *entry.sha())
+            .ok_or_else(|| {
+                Error::Generic(format!("path '{}' does not exist in '{}'", path, rev))
+            })?;
+    }
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{
+        obj::{
+            blob::Blob,
+            commit::Commit,
+            signature::Signature,
+            tag::Tag,
+            tree::{Tree, TreeEntry, TreeEntryMode},
+        },
+        sha::ObjectFormat,
+    };
+
+    #[test]
+    fn test_resolve() {
+        let work_tree = env::temp_dir().join(format!("revision-test-{}", process::id()));
+        let repo = Repository::init(&work_tree, ObjectFormat::Sha1).unwrap();
+        let odb = repo.odb();
+        let signature = Signature::new("A U Thor", "author@example.com", 1700000000, 60);
+
+        let blob = odb
+            .write(&Object::from_blob(Blob::new(b"fn main() {}\n")))
+            .unwrap();
+        let src = odb
+            .write(&Object::from_tree(Tree::new(vec![TreeEntry::new(
+                TreeEntryMode::Blob,
+                "main.rs",
+                blob,
+            )])))
+            .unwrap();
+        let tree = odb
+            .write(&Object::from_tree(Tree::new(vec![TreeEntry::new(
+                TreeEntryMode::Tree,
+                "src",
+                src,
+            )])))
+            .unwrap();
+
+        let commit = |parents: &[ObjectId], msg| {
+            let commit = Commit::new(
+                tree,
+                parents.to_vec(),
+                msg,
+                signature.clone(),
+                signature.clone(),
+            );
+
+            odb.write(&Object::from_commit(commit)).unwrap()
+        };
+        let root = commit(&[], "root");
+        let side = commit(&[root], "side");
+        let first = commit(&[root], "first");
+        let merge = commit(&[first, side], "merge");
+
+        let tag = odb
+            .write(&Object::from_tag(Tag::new(
+                merge,
+                "commit",
+                "v1.0",
+                "release",
+                signature.clone(),
+            )))
+            .unwrap();
+
+        let git_dir = repo.git_dir();
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::create_dir_all(git_dir.join("refs/tags")).unwrap();
+        fs::create_dir_all(git_dir.join("logs/refs/heads")).unwrap();
+        fs::write(git_dir.join("refs/heads/master"), format!("{}\n", merge)).unwrap();
+        fs::write(git_dir.join("refs/heads/side"), format!("{}\n", side)).unwrap();
+        fs::write(git_dir.join("refs/tags/v1.0"), format!("{}\n", tag)).unwrap();
+        fs::write(
+            git_dir.join("logs/refs/heads/master"),
+            format!(
+                "{zero} {root} {sig}\tcommit (initial): root\n\
+                 {root} {first} {sig}\tcommit: first\n\
+                 {first} {merge} {sig}\tcommit: merge\n",
+                zero = "0".repeat(40),
+                sig = signature,
+            ),
+        )
+        .unwrap();
+        fs::write(
+            git_dir.join("logs/HEAD"),
+            format!(
+                "{merge} {side} {sig}\tcheckout: moving from master to side\n\
+                 {side} {merge} {sig}\tcheckout: moving from side to master\n",
+                sig = signature,
+            ),
+        )
+        .unwrap();
+
+        let resolve = |spec: &str| resolve(&repo, spec).unwrap();
+
+        assert_eq!(merge, resolve("HEAD"));
+        assert_eq!(merge, resolve("@"));
+        assert_eq!(merge, resolve(&merge.to_hex()[..7]));
+        assert_eq!(first, resolve("HEAD^"));
+        assert_eq!(side, resolve("master^2"));
+        assert_eq!(root, resolve("HEAD~2"));
+        assert_eq!(root, resolve("HEAD^2~1"));
+        assert_eq!(tag, resolve("v1.0"));
+        assert_eq!(merge, resolve("v1.0^{}"));
+        assert_eq!(merge, resolve("v1.0^0"));
+        assert_eq!(merge, resolve("v1.0^{commit}"));
+        assert_eq!(tree, resolve("master^{tree}"));
+        assert_eq!(blob, resolve("HEAD:src/main.rs"));
+        assert_eq!(src, resolve("v1.0:src"));
+        assert_eq!(side, resolve("@{-1}"));
+        assert_eq!(merge, resolve("master@{0}"));
+        assert_eq!(first, resolve("master@{1}"));
+        assert_eq!(root, resolve("@{2}"));
+        assert_eq!(merge, resolve("master@{now}"));
+        assert_eq!(merge, resolve("master@{2023-11-15T00:00:00Z}"));
+        assert_eq!(root, resolve("master@{2000-01-01T00:00:00Z}"));
+        assert_eq!(merge, resolve("master@{1700000000}"));
+
+        assert!(resolve_rev_err(&repo, "HEAD^3"));
+        assert!(resolve_rev_err(&repo, "HEAD:missing"));
+        assert!(resolve_rev_err(&repo, "master@{3}"));
+        assert!(resolve_rev_err(&repo, "side@{yesterday}"));
+        assert!(resolve_rev_err(&repo, "master@{someday}"));
+        assert!(resolve_rev_err(&repo, "HEAD^{blob}"));
+        assert!(resolve_rev_err(&repo, "nope"));
+        for spec in ["HEAD^é", "HEAD~é", "HEAD^2é", "HEAD^{é}"] {
+            assert!(resolve_rev_err(&repo, spec));
+        }
+
+        fs::remove_dir_all(work_tree).unwrap();
+    }
+
+    fn resolve_rev_err(repo: &Repository, spec: &str) -> bool {
+        resolve(repo, spec).is_err()
+    }
+}